@@ -0,0 +1,53 @@
+-- Schema as it was before versioned migrations. Every statement is
+-- idempotent so databases created by older versions can adopt it.
+
+CREATE TABLE IF NOT EXISTS roles (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    permissions TEXT NOT NULL,
+    level INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS role_parents (
+    role_name TEXT NOT NULL,
+    parent_name TEXT NOT NULL,
+    PRIMARY KEY(role_name, parent_name),
+    FOREIGN KEY(role_name) REFERENCES roles(name),
+    FOREIGN KEY(parent_name) REFERENCES roles(name)
+);
+
+CREATE TABLE IF NOT EXISTS player_roles (
+    id INTEGER PRIMARY KEY,
+    player_uuid TEXT NOT NULL,
+    role_name TEXT NOT NULL,
+    expires_at INTEGER,
+    contexts TEXT NOT NULL DEFAULT '',
+    FOREIGN KEY(role_name) REFERENCES roles(name)
+);
+
+CREATE TABLE IF NOT EXISTS player_permissions (
+    id INTEGER PRIMARY KEY,
+    player_uuid TEXT NOT NULL,
+    permission TEXT NOT NULL,
+    expires_at INTEGER,
+    contexts TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    subject TEXT,
+    old_value TEXT,
+    new_value TEXT,
+    reason TEXT,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_target ON audit_log (target, created_at);
+CREATE INDEX IF NOT EXISTS audit_log_actor ON audit_log (actor, created_at);
+
+-- Latest applied migration, for inspecting a database by hand
+CREATE VIEW IF NOT EXISTS schema_version AS
+    SELECT COALESCE(MAX(version), 0) AS version FROM _sqlx_migrations WHERE success = 1;
@@ -0,0 +1,24 @@
+-- Role permissions move from the JSON array in roles.permissions into rows,
+-- one per node and context set.
+
+CREATE TABLE role_permissions (
+    id INTEGER PRIMARY KEY,
+    role_id INTEGER NOT NULL,
+    permission TEXT NOT NULL,
+    contexts TEXT NOT NULL DEFAULT '',
+    UNIQUE(role_id, permission, contexts),
+    FOREIGN KEY(role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+CREATE INDEX role_permissions_permission ON role_permissions (permission);
+
+-- Entries are either a plain node string or {"node": ..., "context": ...}
+INSERT OR IGNORE INTO role_permissions (role_id, permission, contexts)
+SELECT
+    roles.id,
+    CASE entry.type WHEN 'text' THEN entry.value ELSE json_extract(entry.value, '$.node') END,
+    CASE entry.type WHEN 'text' THEN '' ELSE COALESCE(json_extract(entry.value, '$.context'), '') END
+FROM roles, json_each(roles.permissions) AS entry
+WHERE json_valid(roles.permissions);
+
+ALTER TABLE roles DROP COLUMN permissions;
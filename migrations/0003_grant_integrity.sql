@@ -0,0 +1,50 @@
+-- Foreign keys are enforced on every connection from here on. Memberships
+-- and inheritance links follow role renames, and the same role or node can
+-- only be held once per player and context set.
+
+-- Rows pointing at roles that no longer exist would fail the checks
+DELETE FROM role_parents
+WHERE role_name NOT IN (SELECT name FROM roles) OR parent_name NOT IN (SELECT name FROM roles);
+DELETE FROM role_permissions WHERE role_id NOT IN (SELECT id FROM roles);
+
+CREATE TABLE role_parents_new (
+    role_name TEXT NOT NULL,
+    parent_name TEXT NOT NULL,
+    PRIMARY KEY(role_name, parent_name),
+    FOREIGN KEY(role_name) REFERENCES roles(name) ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY(parent_name) REFERENCES roles(name) ON UPDATE CASCADE ON DELETE CASCADE
+);
+INSERT INTO role_parents_new (role_name, parent_name) SELECT role_name, parent_name FROM role_parents;
+DROP TABLE role_parents;
+ALTER TABLE role_parents_new RENAME TO role_parents;
+
+CREATE TABLE player_roles_new (
+    id INTEGER PRIMARY KEY,
+    player_uuid TEXT NOT NULL,
+    role_name TEXT NOT NULL,
+    expires_at INTEGER,
+    contexts TEXT NOT NULL DEFAULT '',
+    UNIQUE(player_uuid, role_name, contexts),
+    FOREIGN KEY(role_name) REFERENCES roles(name) ON UPDATE CASCADE
+);
+-- Of duplicates, the longest lasting row is inserted first and kept
+INSERT OR IGNORE INTO player_roles_new (id, player_uuid, role_name, expires_at, contexts)
+SELECT id, player_uuid, role_name, expires_at, contexts FROM player_roles
+WHERE role_name IN (SELECT name FROM roles)
+ORDER BY expires_at IS NULL DESC, expires_at DESC, id;
+DROP TABLE player_roles;
+ALTER TABLE player_roles_new RENAME TO player_roles;
+
+CREATE TABLE player_permissions_new (
+    id INTEGER PRIMARY KEY,
+    player_uuid TEXT NOT NULL,
+    permission TEXT NOT NULL,
+    expires_at INTEGER,
+    contexts TEXT NOT NULL DEFAULT '',
+    UNIQUE(player_uuid, permission, contexts)
+);
+INSERT OR IGNORE INTO player_permissions_new (id, player_uuid, permission, expires_at, contexts)
+SELECT id, player_uuid, permission, expires_at, contexts FROM player_permissions
+ORDER BY expires_at IS NULL DESC, expires_at DESC, id;
+DROP TABLE player_permissions;
+ALTER TABLE player_permissions_new RENAME TO player_permissions;
@@ -0,0 +1,98 @@
+// Argument consumers that read one word like `SimpleArgConsumer`, but ask the
+// server for suggestions so players can tab-complete what the plugin knows.
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{Arg, ArgumentConsumer, GetClientSideArgParser},
+        dispatcher::CommandError,
+        tree::RawArgs,
+        CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_protocol::client::play::{ArgumentType, CommandSuggestion, StringProto, SuggestionProviders};
+
+use crate::permissions::{known, snapshot};
+
+/// A role name, suggested from the roles in the permission snapshot.
+pub struct RoleArgumentConsumer;
+
+/// A permission node, suggested segment by segment from the known nodes.
+pub struct PermissionNodeArgumentConsumer;
+
+impl GetClientSideArgParser for RoleArgumentConsumer {
+    fn get_client_side_parser(&self) -> ArgumentType {
+        ArgumentType::String(StringProto::SingleWord)
+    }
+
+    fn get_client_side_suggestion_type_override(&self) -> Option<SuggestionProviders> {
+        Some(SuggestionProviders::AskServer)
+    }
+}
+
+#[async_trait]
+impl ArgumentConsumer for RoleArgumentConsumer {
+    async fn consume<'a>(
+        &'a self,
+        _sender: &CommandSender<'a>,
+        _server: &'a Server,
+        args: &mut RawArgs<'a>,
+    ) -> Option<Arg<'a>> {
+        Some(Arg::Simple(args.pop()?))
+    }
+
+    async fn suggest<'a>(
+        &'a self,
+        _sender: &CommandSender<'a>,
+        _server: &'a Server,
+        input: &'a str,
+    ) -> Result<Option<Vec<CommandSuggestion>>, CommandError> {
+        // Read from the in-memory snapshot, so completing never queries SQLite
+        let snapshot = snapshot::current();
+        let mut names: Vec<&String> = snapshot.roles.keys().collect();
+        names.sort();
+
+        let input = input.to_lowercase();
+        let suggestions = names
+            .into_iter()
+            .filter(|name| name.to_lowercase().starts_with(&input))
+            .map(|name| CommandSuggestion::new(name.clone(), None))
+            .collect();
+        Ok(Some(suggestions))
+    }
+}
+
+impl GetClientSideArgParser for PermissionNodeArgumentConsumer {
+    fn get_client_side_parser(&self) -> ArgumentType {
+        ArgumentType::String(StringProto::SingleWord)
+    }
+
+    fn get_client_side_suggestion_type_override(&self) -> Option<SuggestionProviders> {
+        Some(SuggestionProviders::AskServer)
+    }
+}
+
+#[async_trait]
+impl ArgumentConsumer for PermissionNodeArgumentConsumer {
+    async fn consume<'a>(
+        &'a self,
+        _sender: &CommandSender<'a>,
+        _server: &'a Server,
+        args: &mut RawArgs<'a>,
+    ) -> Option<Arg<'a>> {
+        Some(Arg::Simple(args.pop()?))
+    }
+
+    async fn suggest<'a>(
+        &'a self,
+        _sender: &CommandSender<'a>,
+        _server: &'a Server,
+        input: &'a str,
+    ) -> Result<Option<Vec<CommandSuggestion>>, CommandError> {
+        let suggestions = known::suggest(input)
+            .into_iter()
+            .map(|node| CommandSuggestion::new(node, None))
+            .collect();
+        Ok(Some(suggestions))
+    }
+}
@@ -12,4 +12,5 @@ pub trait Command: CommandExecutor {
     fn init_command() -> CommandTree where Self: Sized;
 }
 
+pub mod args;
 pub mod perms;
\ No newline at end of file
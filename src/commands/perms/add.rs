@@ -9,7 +9,8 @@ use pumpkin::{
 };
 use pumpkin_util::text::TextComponent;
 
-use crate::{permissions, utils::success_colour, get_runtime};
+use super::{describe_contexts, grant_options, refuse};
+use crate::{permissions::{self, authority::Actor, node}, utils::{self, success_colour, error_colour}, get_runtime};
 
 pub struct PermsAddCommand;
 
@@ -27,25 +28,69 @@ impl CommandExecutor for PermsAddCommand {
         let Some(Arg::Simple(permission)) = args.get("permission") else {
             return Err(CommandError::InvalidConsumption(Some("permission".into())));
         };
+        if let Err(problem) = node::check_syntax(permission) {
+            sender
+                .send_message(TextComponent::text(format!("Invalid permission '{}': {}", permission, problem)).color_rgb(error_colour()))
+                .await;
+            return Ok(());
+        }
+
+        let (duration, contexts) = match grant_options(args) {
+            Ok(options) => options,
+            Err(message) => {
+                sender
+                    .send_message(TextComponent::text(message).color_rgb(error_colour()))
+                    .await;
+                return Ok(());
+            }
+        };
 
         let player = &targets[0];
         let player_uuid = player.gameprofile.id;
         let permission_str = permission.to_string();
+        let expires_at = duration.map(|seconds| utils::unix_now() + seconds);
+        let scope = describe_contexts(&contexts);
+
+        let actor = Actor::from_sender(sender);
+        if let Err(denied) = actor
+            .check_player(&player_uuid, &player.gameprofile.name)
+            .and_then(|()| actor.check_node(permission, &contexts))
+        {
+            refuse(sender, &actor, &player_uuid.to_string(), &format!("adding {} to {}", permission, player.gameprofile.name), denied).await;
+            return Ok(());
+        }
 
         // Execute database operation in our runtime
         let runtime = get_runtime();
-        if let Err(e) = runtime.spawn(async move {
-            permissions::add_player_permission(&player_uuid, &permission_str).await
+        let added = match runtime.spawn(async move {
+            permissions::add_player_permission(&actor, &player_uuid, &permission_str, expires_at, &contexts).await
         }).await.unwrap() {
-            log::error!("Failed to add permission: {}", e);
+            Ok(added) => added,
+            Err(e) => {
+                log::error!("Failed to add permission: {}", e);
+                return Ok(());
+            }
+        };
+
+        if !added {
+            sender
+                .send_message(TextComponent::text(format!(
+                    "{} already has permission {}{}",
+                    player.gameprofile.name, permission, scope
+                )).color_rgb(error_colour()))
+                .await;
             return Ok(());
         }
 
+        let message = match duration {
+            Some(seconds) => format!(
+                "Added permission {}{} to {} for {}",
+                permission, scope, player.gameprofile.name, utils::format_duration(seconds)
+            ),
+            None => format!("Added permission {}{} to {}", permission, scope, player.gameprofile.name),
+        };
         sender
-            .send_message(TextComponent::text(format!(
-                "Added permission {} to {}",
-                permission, player.gameprofile.name
-            )).color_rgb(success_colour()))
+            .send_message(TextComponent::text(message).color_rgb(success_colour()))
             .await;
         Ok(())
     }
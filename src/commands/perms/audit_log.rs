@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{Arg, ConsumedArgs},
+        dispatcher::CommandError,
+        CommandExecutor, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+use uuid::Uuid;
+
+use crate::{
+    permissions::audit::{self, AuditEntry, AuditFilter},
+    utils::{self, success_colour, error_colour, neutral_colour},
+    get_runtime,
+};
+
+const PAGE_SIZE: u32 = 10;
+
+/// Names of the optional trailing arguments, read in order.
+pub const LOG_ARGS: [&str; 4] = ["query1", "query2", "query3", "query4"];
+
+pub struct PermsLogCommand;
+
+/// Resolves a player name to the UUID stored in the log. Online players are
+/// looked up by name; anything else is kept as written, so UUIDs, role names
+/// and `console` match directly.
+pub(super) async fn resolve_name(server: &Server, name: &str) -> String {
+    if Uuid::parse_str(name).is_ok() {
+        return name.to_string();
+    }
+    match server.get_player_by_name(name).await {
+        Some(player) => player.gameprofile.id.to_string(),
+        None => name.to_string(),
+    }
+}
+
+/// Shows stored UUIDs as player names when the player is online.
+pub(super) async fn display_name(server: &Server, stored: &str) -> String {
+    let Ok(uuid) = Uuid::parse_str(stored) else {
+        return stored.to_string();
+    };
+    match server.get_player_by_uuid(uuid).await {
+        Some(player) => player.gameprofile.name.clone(),
+        None => stored.to_string(),
+    }
+}
+
+// e.g. `#42 3h 5m ago, console: player.role.add Steve admin (track staff)`
+async fn describe_entry(server: &Server, entry: &AuditEntry, now: i64) -> String {
+    let mut description = format!(
+        "#{} {} ago, {}: {} {}",
+        entry.id,
+        utils::format_duration(now - entry.created_at),
+        display_name(server, &entry.actor).await,
+        entry.action,
+        display_name(server, &entry.target).await,
+    );
+    if let Some(subject) = &entry.subject {
+        description.push_str(&format!(" {}", subject));
+    }
+    // Structured values are kept for undo and would only clutter the line
+    let plain = |value: &Option<String>| value.clone().filter(|value| !value.is_empty() && !value.starts_with(['[', '{']));
+    match (plain(&entry.old_value), plain(&entry.new_value)) {
+        (Some(old), Some(new)) => description.push_str(&format!(" [{} -> {}]", old, new)),
+        (None, Some(new)) => description.push_str(&format!(" [{}]", new)),
+        _ => {},
+    }
+    if let Some(reason) = &entry.reason {
+        description.push_str(&format!(" ({})", reason));
+    }
+    description
+}
+
+#[async_trait]
+impl CommandExecutor for PermsLogCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let now = utils::unix_now();
+        let mut filter = AuditFilter::default();
+        let mut page: u32 = 1;
+
+        // Each argument is a page number, `actor:<name>`, `since:<duration>`,
+        // `until:<duration>` or else the player or role to show
+        for name in LOG_ARGS {
+            let Some(Arg::Simple(value)) = args.get(name) else {
+                continue;
+            };
+
+            let parsed = if let Ok(number) = value.parse::<u32>() {
+                page = number.max(1);
+                Ok(())
+            } else if let Some(actor) = value.strip_prefix("actor:") {
+                filter.actor = Some(resolve_name(server, actor).await);
+                Ok(())
+            } else if let Some(duration) = value.strip_prefix("since:") {
+                utils::parse_duration(duration)
+                    .map(|seconds| filter.since = Some(now - seconds))
+                    .ok_or(duration)
+            } else if let Some(duration) = value.strip_prefix("until:") {
+                utils::parse_duration(duration)
+                    .map(|seconds| filter.until = Some(now - seconds))
+                    .ok_or(duration)
+            } else {
+                filter.target = Some(resolve_name(server, value).await);
+                Ok(())
+            };
+
+            if let Err(duration) = parsed {
+                sender
+                    .send_message(TextComponent::text(format!(
+                        "Invalid duration '{}'. Use e.g. 30m, 12h or 7d, up to 100 years", duration
+                    )).color_rgb(error_colour()))
+                    .await;
+                return Ok(());
+            }
+        }
+
+        let query_filter = filter.clone();
+        let (entries, total) = match get_runtime().spawn(async move {
+            audit::query(&query_filter, page, PAGE_SIZE).await
+        }).await.unwrap() {
+            Ok(result) => result,
+            Err(e) => {
+                log::error!("Failed to read audit log: {}", e);
+                return Ok(());
+            }
+        };
+
+        if entries.is_empty() {
+            sender
+                .send_message(TextComponent::text("No audit entries found").color_rgb(neutral_colour()))
+                .await;
+            return Ok(());
+        }
+
+        let pages = (total as u32).div_ceil(PAGE_SIZE);
+        sender
+            .send_message(TextComponent::text(format!(
+                "=== Audit log (page {}/{}, {} entries) ===", page, pages, total
+            )).color_rgb(success_colour()))
+            .await;
+
+        for entry in &entries {
+            sender
+                .send_message(TextComponent::text(describe_entry(server, entry, now).await).color_rgb(neutral_colour()))
+                .await;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::ConsumedArgs,
+        dispatcher::CommandError,
+        CommandExecutor, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+
+use crate::{
+    config::{self, validate::{self, Severity}},
+    utils::{success_colour, error_colour, neutral_colour},
+};
+
+/// `/perms config check`: validates config.toml as it is on disk without
+/// applying anything.
+pub struct PermsConfigCheckCommand;
+
+#[async_trait]
+impl CommandExecutor for PermsConfigCheckCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        _args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let config = config::get_config().await;
+        let content = match tokio::fs::read_to_string(config.path()).await {
+            Ok(content) => content,
+            Err(e) => {
+                sender
+                    .send_message(TextComponent::text(format!("Failed to read {}: {}", config.path(), e)).color_rgb(error_colour()))
+                    .await;
+                return Ok(());
+            }
+        };
+
+        let report = validate::check(&content);
+        if report.diagnostics.is_empty() {
+            sender
+                .send_message(TextComponent::text("config.toml has no problems").color_rgb(success_colour()))
+                .await;
+            return Ok(());
+        }
+
+        let errors = report.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+        let summary = format!(
+            "config.toml: {} error(s), {} warning(s){}",
+            errors,
+            report.diagnostics.len() - errors,
+            if errors > 0 { ", it would not be applied" } else { "" }
+        );
+        sender
+            .send_message(TextComponent::text(summary).color_rgb(if errors > 0 { error_colour() } else { success_colour() }))
+            .await;
+
+        for diagnostic in &report.diagnostics {
+            let colour = match diagnostic.severity {
+                Severity::Error => error_colour(),
+                Severity::Warning => neutral_colour(),
+            };
+            sender
+                .send_message(TextComponent::text(diagnostic.to_string()).color_rgb(colour))
+                .await;
+        }
+        Ok(())
+    }
+}
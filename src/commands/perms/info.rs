@@ -9,10 +9,22 @@ use pumpkin::{
 };
 use pumpkin_util::text::TextComponent;
 
-use crate::{permissions, utils::{self, success_colour, neutral_colour}, get_runtime};
+use crate::{permissions::{self, context::ContextSet}, utils::{self, success_colour, neutral_colour}, get_runtime};
 
 pub struct PermsInfoCommand;
 
+// e.g. `worldedit.* [world=creative] (expires in 6d 23h)`
+fn describe_grant(name: &str, expires_at: Option<i64>, contexts: &ContextSet, now: i64) -> String {
+    let mut description = name.to_string();
+    if !contexts.is_empty() {
+        description.push_str(&format!(" [{}]", contexts));
+    }
+    if let Some(expires_at) = expires_at {
+        description.push_str(&format!(" (expires in {})", utils::format_duration(expires_at - now)));
+    }
+    description
+}
+
 #[async_trait]
 impl CommandExecutor for PermsInfoCommand {
     async fn execute<'a>(
@@ -40,15 +52,20 @@ impl CommandExecutor for PermsInfoCommand {
                         .color_rgb(success_colour())
                 ).await;
 
-                // Show roles
+                // Show roles, with time left on temporary memberships
+                let now = utils::unix_now();
                 if perms.roles.is_empty() {
                     sender.send_message(
                         TextComponent::text("Roles: None")
                             .color_rgb(neutral_colour())
                     ).await;
                 } else {
+                    let roles: Vec<String> = perms.roles
+                        .iter()
+                        .map(|membership| describe_grant(&membership.role, membership.expires_at, &membership.contexts, now))
+                        .collect();
                     sender.send_message(
-                        TextComponent::text(format!("Roles: {}", perms.roles.join(", ")))
+                        TextComponent::text(format!("Roles: {}", roles.join(", ")))
                             .color_rgb(neutral_colour())
                     ).await;
                 }
@@ -60,8 +77,12 @@ impl CommandExecutor for PermsInfoCommand {
                             .color_rgb(neutral_colour())
                     ).await;
                 } else {
+                    let direct_permissions: Vec<String> = perms.direct_permissions
+                        .iter()
+                        .map(|grant| describe_grant(&grant.permission, grant.expires_at, &grant.contexts, now))
+                        .collect();
                     sender.send_message(
-                        TextComponent::text(format!("Direct Permissions: {}", perms.direct_permissions.join(", ")))
+                        TextComponent::text(format!("Direct Permissions: {}", direct_permissions.join(", ")))
                             .color_rgb(neutral_colour())
                     ).await;
                 }
@@ -1,11 +1,19 @@
 mod add;
+mod remove;
 mod role;
+mod role_manage;
+mod role_perm;
 mod info;
+mod audit_log;
+mod rollback;
+mod reload;
+mod config_check;
+mod track;
 
 use async_trait::async_trait;
 use pumpkin::{
     command::{
-        args::{players::PlayersArgumentConsumer, simple::SimpleArgConsumer},
+        args::{players::PlayersArgumentConsumer, simple::SimpleArgConsumer, Arg, ConsumedArgs},
         dispatcher::CommandError,
         tree::CommandTree,
         tree_builder::{argument, literal, require},
@@ -13,29 +21,153 @@ use pumpkin::{
     },
     server::Server,
 };
-use pumpkin_util::text::TextComponent;
+use pumpkin_util::{permission::PermissionLvl, text::TextComponent};
 
 pub use add::PermsAddCommand;
-pub use role::PermsRoleCommand;
+pub use remove::PermsRemoveCommand;
+pub use role::{PermsRoleCommand, RoleAction};
+pub use role_manage::{PermsRoleCloneCommand, PermsRoleCreateCommand, PermsRoleDeleteCommand, PermsRoleRenameCommand};
+pub use role_perm::{PermsRolePermAddCommand, PermsRolePermListCommand, PermsRolePermRemoveCommand};
 pub use info::PermsInfoCommand;
+pub use audit_log::PermsLogCommand;
+pub use rollback::{PermsRollbackCommand, PermsUndoCommand};
+pub use reload::PermsReloadCommand;
+pub use config_check::PermsConfigCheckCommand;
+pub use track::PermsTrackCommand;
 
-use crate::{utils::success_colour, commands::Command};
+use crate::{
+    commands::args::{PermissionNodeArgumentConsumer, RoleArgumentConsumer},
+    permissions::{audit, authority::{Actor, Denied}, cache, context::ContextSet, track::TrackDirection},
+    utils::{self, success_colour, error_colour, neutral_colour},
+    commands::Command,
+    config,
+    get_runtime,
+};
 
 pub struct PermsCommand;
 
+/// Namespace of the nodes gating /perms. The bare node allows `/perms` itself.
+pub const NAMESPACE: &str = "hysterion.perms";
+
+/// Node gating a subcommand, e.g. `hysterion.perms.role.create`.
+pub fn node(subcommand: &str) -> String {
+    format!("{}.{}", NAMESPACE, subcommand)
+}
+
+// Requirement passed by holding `node`, or by the configured vanilla op level
+fn gate(node: String) -> impl Fn(&CommandSender) -> bool + Send + Sync + 'static {
+    move |sender| sender.has_permission(&node) || sender.has_permission_lvl(op_level(config::op_level()))
+}
+
+fn op_level(level: u8) -> PermissionLvl {
+    match level {
+        0 => PermissionLvl::Zero,
+        1 => PermissionLvl::One,
+        2 => PermissionLvl::Two,
+        3 => PermissionLvl::Three,
+        _ => PermissionLvl::Four,
+    }
+}
+
+/// Reads the optional trailing `duration` and `context` arguments of a grant.
+///
+/// Either may be given alone: a value containing `=` is read as contexts
+/// (`world=creative,gamemode=creative`), anything else as a duration.
+fn grant_options(args: &ConsumedArgs<'_>) -> Result<(Option<i64>, ContextSet), String> {
+    let mut duration = None;
+    let mut contexts = ContextSet::new();
+
+    for name in ["duration", "context"] {
+        let Some(Arg::Simple(value)) = args.get(name) else {
+            continue;
+        };
+        if value.contains('=') {
+            contexts = ContextSet::parse(value)?;
+        } else {
+            duration = Some(utils::parse_duration(value).ok_or_else(|| {
+                format!("Invalid duration '{}'. Use e.g. 30m, 12h or 7d, up to 100 years", value)
+            })?);
+        }
+    }
+
+    Ok((duration, contexts))
+}
+
+/// Formats contexts for feedback messages, e.g. ` in world=creative`.
+fn describe_contexts(contexts: &ContextSet) -> String {
+    if contexts.is_empty() {
+        String::new()
+    } else {
+        format!(" in {}", contexts)
+    }
+}
+
+/// Error for a role that does not exist, suggesting likely typos from `known`.
+fn unknown_role_message(role: &str, known: &[String]) -> String {
+    let suggestions = utils::closest_matches(role, known, 3);
+    if suggestions.is_empty() {
+        format!("Role {} does not exist", role)
+    } else {
+        format!("Role {} does not exist. Did you mean {}?", role, suggestions.join(", "))
+    }
+}
+
+/// Writes `roles` back to config.toml after a command changed them, when
+/// write-through is enabled; `renamed` holds the old and new names of any
+/// renames, in order. The change itself already succeeded, so a failure here
+/// is only logged.
+async fn write_through(roles: &[&str], renamed: &[(&str, &str)]) {
+    let names: Vec<String> = roles.iter().map(|role| role.to_string()).collect();
+    let renamed: Vec<(String, String)> = renamed.iter().map(|(old, new)| (old.to_string(), new.to_string())).collect();
+    if let Err(e) = get_runtime().spawn(async move {
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let renamed: Vec<(&str, &str)> = renamed.iter().map(|(old, new)| (old.as_str(), new.as_str())).collect();
+        config::write_through(&names, &renamed).await.map_err(|e| e.to_string())
+    }).await.unwrap() {
+        log::error!("[HysterionPerms] Failed to write roles back to config.toml: {}", e);
+    }
+}
+
+/// Tells the sender why a change was refused and records the attempt in the
+/// audit log against `target`, a player UUID or role name.
+async fn refuse(sender: &CommandSender<'_>, actor: &Actor, target: &str, action: &str, denied: Denied) {
+    log::warn!("[HysterionPerms] Denied {} by {}: {}", action, actor, denied);
+
+    let (actor_owned, target_owned, action_owned, reason) = (*actor, target.to_string(), action.to_string(), denied.to_string());
+    if let Err(e) = get_runtime().spawn(async move {
+        audit::record_denied(&actor_owned, &target_owned, &action_owned, &reason).await
+    }).await.unwrap() {
+        log::error!("Failed to record denied change: {}", e);
+    }
+
+    sender
+        .send_message(TextComponent::text(denied.to_string()).color_rgb(error_colour()))
+        .await;
+}
+
 #[async_trait]
 impl CommandExecutor for PermsCommand {
     async fn execute<'a>(
         &self,
         sender: &mut CommandSender<'a>,
         _: &Server,
-        _: &pumpkin::command::args::ConsumedArgs<'a>,
+        _: &ConsumedArgs<'a>,
     ) -> Result<(), CommandError> {
         sender
             .send_message(TextComponent::text(
                 "Hysterion Permissions Plugin"
             ).color_rgb(success_colour()))
             .await;
+
+        let stats = cache::stats();
+        let lookups = stats.hits + stats.misses;
+        let hit_rate = if lookups == 0 { 0.0 } else { stats.hits as f64 * 100.0 / lookups as f64 };
+        sender
+            .send_message(TextComponent::text(format!(
+                "Permission cache: {} players, {} hits, {} misses ({:.1}% hit rate)",
+                stats.entries, stats.hits, stats.misses, hit_rate
+            )).color_rgb(neutral_colour()))
+            .await;
         Ok(())
     }
 }
@@ -49,21 +181,121 @@ impl Command for PermsCommand {
         "Manage permissions"
     }
 
+    /// The whole tree sits behind a `require` on [`NAMESPACE`] itself, and
+    /// every subcommand behind another on its own node under it, e.g.
+    /// `hysterion.perms.add`; senders with the configured op level pass them all.
     fn init_command() -> CommandTree where Self: Sized {
         CommandTree::new([Self.get_name()], Self.get_description())
-            .then(require(|sender| sender.has_permission(&format!("hysterion_perms.{}", Self.get_name())))
+            .then(require(gate(NAMESPACE.to_string()))
                 .execute(PermsCommand)
-                .then(literal("add")
-                    .then(argument("player", PlayersArgumentConsumer)
-                        .then(argument("permission", SimpleArgConsumer)
-                            .execute(PermsAddCommand))))
+                .then(require(gate(node("add")))
+                    .then(literal("add")
+                        .then(argument("player", PlayersArgumentConsumer)
+                            .then(argument("permission", PermissionNodeArgumentConsumer)
+                                .execute(PermsAddCommand)
+                                .then(argument("duration", SimpleArgConsumer)
+                                    .execute(PermsAddCommand)
+                                    .then(argument("context", SimpleArgConsumer)
+                                        .execute(PermsAddCommand)))))))
+                .then(require(gate(node("remove")))
+                    .then(literal("remove")
+                        .then(argument("player", PlayersArgumentConsumer)
+                            .then(argument("permission", PermissionNodeArgumentConsumer)
+                                .execute(PermsRemoveCommand)))))
                 .then(literal("role")
-                    .then(argument("role_action", SimpleArgConsumer)
+                    .then(require(gate(node("role.create")))
+                        .then(literal("create")
+                            .then(argument("name", SimpleArgConsumer)
+                                .then(argument("level", SimpleArgConsumer)
+                                    .execute(PermsRoleCreateCommand)))))
+                    .then(require(gate(node("role.delete")))
+                        .then(literal("delete")
+                            .then(argument("name", RoleArgumentConsumer)
+                                .execute(PermsRoleDeleteCommand)
+                                .then(argument("fallback", RoleArgumentConsumer)
+                                    .execute(PermsRoleDeleteCommand)))))
+                    .then(require(gate(node("role.rename")))
+                        .then(literal("rename")
+                            .then(argument("old", RoleArgumentConsumer)
+                                .then(argument("new", SimpleArgConsumer)
+                                    .execute(PermsRoleRenameCommand)))))
+                    .then(require(gate(node("role.clone")))
+                        .then(literal("clone")
+                            .then(argument("source", RoleArgumentConsumer)
+                                .then(argument("target", SimpleArgConsumer)
+                                    .execute(PermsRoleCloneCommand)))))
+                    .then(require(gate(node("role.perm")))
+                        .then(literal("perm")
+                            .then(literal("add")
+                                .then(argument("role", RoleArgumentConsumer)
+                                    .then(argument("permission", PermissionNodeArgumentConsumer)
+                                        .execute(PermsRolePermAddCommand)
+                                        .then(argument("context", SimpleArgConsumer)
+                                            .execute(PermsRolePermAddCommand)))))
+                            .then(literal("remove")
+                                .then(argument("role", RoleArgumentConsumer)
+                                    .then(argument("permission", PermissionNodeArgumentConsumer)
+                                        .execute(PermsRolePermRemoveCommand))))
+                            .then(literal("list")
+                                .then(argument("role", RoleArgumentConsumer)
+                                    .execute(PermsRolePermListCommand)))))
+                    .then(require(gate(node("role.assign")))
+                        .then(literal("add")
+                            .then(argument("player", PlayersArgumentConsumer)
+                                .then(argument("role", RoleArgumentConsumer)
+                                    .execute(PermsRoleCommand(RoleAction::Add))
+                                    .then(argument("duration", SimpleArgConsumer)
+                                        .execute(PermsRoleCommand(RoleAction::Add))
+                                        .then(argument("context", SimpleArgConsumer)
+                                            .execute(PermsRoleCommand(RoleAction::Add)))))))
+                        .then(literal("remove")
+                            .then(argument("player", PlayersArgumentConsumer)
+                                .then(argument("role", RoleArgumentConsumer)
+                                    .execute(PermsRoleCommand(RoleAction::Remove)))))))
+                .then(require(gate(node("promote")))
+                    .then(literal("promote")
+                        .then(argument("player", PlayersArgumentConsumer)
+                            .then(argument("track", SimpleArgConsumer)
+                                .execute(PermsTrackCommand(TrackDirection::Promote))))))
+                .then(require(gate(node("demote")))
+                    .then(literal("demote")
+                        .then(argument("player", PlayersArgumentConsumer)
+                            .then(argument("track", SimpleArgConsumer)
+                                .execute(PermsTrackCommand(TrackDirection::Demote))))))
+                .then(require(gate(node("log")))
+                    .then(literal("log")
+                        .execute(PermsLogCommand)
+                        .then(argument(audit_log::LOG_ARGS[0], SimpleArgConsumer)
+                            .execute(PermsLogCommand)
+                            .then(argument(audit_log::LOG_ARGS[1], SimpleArgConsumer)
+                                .execute(PermsLogCommand)
+                                .then(argument(audit_log::LOG_ARGS[2], SimpleArgConsumer)
+                                    .execute(PermsLogCommand)
+                                    .then(argument(audit_log::LOG_ARGS[3], SimpleArgConsumer)
+                                        .execute(PermsLogCommand)))))))
+                .then(require(gate(node("rollback")))
+                    .then(literal("rollback")
+                        .then(argument("actor", SimpleArgConsumer)
+                            .then(argument("since", SimpleArgConsumer)
+                                .execute(PermsRollbackCommand { confirm: false })
+                                .then(literal("confirm")
+                                    .execute(PermsRollbackCommand { confirm: true }))))))
+                .then(require(gate(node("undo")))
+                    .then(literal("undo")
+                        .then(argument("entry", SimpleArgConsumer)
+                            .execute(PermsUndoCommand { confirm: false })
+                            .then(literal("confirm")
+                                .execute(PermsUndoCommand { confirm: true })))))
+                .then(require(gate(node("reload")))
+                    .then(literal("reload")
+                        .execute(PermsReloadCommand)))
+                .then(require(gate(node("config")))
+                    .then(literal("config")
+                        .then(literal("check")
+                            .execute(PermsConfigCheckCommand))))
+                .then(require(gate(node("info")))
+                    .then(literal("info")
                         .then(argument("player", PlayersArgumentConsumer)
-                            .then(argument("role", SimpleArgConsumer)
-                                .execute(PermsRoleCommand)))))
-                .then(literal("info")
-                    .then(argument("player", PlayersArgumentConsumer)
-                        .execute(PermsInfoCommand))))
+                            .execute(PermsInfoCommand)))))
     }
 } 
\ No newline at end of file
@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::ConsumedArgs,
+        dispatcher::CommandError,
+        CommandExecutor, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+
+use crate::{
+    config,
+    permissions::authority::Actor,
+    utils::{success_colour, error_colour},
+    get_runtime,
+};
+
+/// `/perms reload`
+pub struct PermsReloadCommand;
+
+#[async_trait]
+impl CommandExecutor for PermsReloadCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        _args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        log::info!("[HysterionPerms] Config reload requested by {}", Actor::from_sender(sender));
+
+        match get_runtime().spawn(async move {
+            config::reload().await
+        }).await.unwrap() {
+            Ok(config) => {
+                sender
+                    .send_message(TextComponent::text(format!(
+                        "Reloaded config.toml: {} role(s), {} track(s), reconcile mode {}",
+                        config.value.roles.len(),
+                        config.value.tracks.len(),
+                        config.value.reconcile.as_str()
+                    )).color_rgb(success_colour()))
+                    .await;
+            },
+            Err(e) => {
+                log::error!("[HysterionPerms] Failed to reload config: {}", e);
+                sender
+                    .send_message(TextComponent::text(
+                        "config.toml was not reloaded and the previous config is still active"
+                    ).color_rgb(error_colour()))
+                    .await;
+                for diagnostic in &e.0 {
+                    sender
+                        .send_message(TextComponent::text(diagnostic.to_string()).color_rgb(error_colour()))
+                        .await;
+                }
+            }
+        }
+        Ok(())
+    }
+}
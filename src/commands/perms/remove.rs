@@ -0,0 +1,71 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{Arg, ConsumedArgs},
+        dispatcher::CommandError,
+        CommandExecutor, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+
+use super::refuse;
+use crate::{permissions::{self, authority::Actor}, utils::{success_colour, error_colour}, get_runtime};
+
+pub struct PermsRemoveCommand;
+
+#[async_trait]
+impl CommandExecutor for PermsRemoveCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let Some(Arg::Players(targets)) = args.get("player") else {
+            return Err(CommandError::InvalidConsumption(Some("player".into())));
+        };
+        let Some(Arg::Simple(permission)) = args.get("permission") else {
+            return Err(CommandError::InvalidConsumption(Some("permission".into())));
+        };
+
+        let player = &targets[0];
+        let player_uuid = player.gameprofile.id;
+        let permission_str = permission.to_string();
+
+        let actor = Actor::from_sender(sender);
+        if let Err(denied) = actor.check_player(&player_uuid, &player.gameprofile.name) {
+            refuse(sender, &actor, &player_uuid.to_string(), &format!("removing {} from {}", permission, player.gameprofile.name), denied).await;
+            return Ok(());
+        }
+
+        // Execute database operation in our runtime
+        let runtime = get_runtime();
+        let removed = match runtime.spawn(async move {
+            permissions::remove_player_permission(&actor, &player_uuid, &permission_str).await
+        }).await.unwrap() {
+            Ok(removed) => removed,
+            Err(e) => {
+                log::error!("Failed to remove permission: {}", e);
+                return Ok(());
+            }
+        };
+
+        if removed {
+            sender
+                .send_message(TextComponent::text(format!(
+                    "Removed permission {} from {}",
+                    permission, player.gameprofile.name
+                )).color_rgb(success_colour()))
+                .await;
+        } else {
+            sender
+                .send_message(TextComponent::text(format!(
+                    "{} does not have permission {} granted directly",
+                    player.gameprofile.name, permission
+                )).color_rgb(error_colour()))
+                .await;
+        }
+        Ok(())
+    }
+}
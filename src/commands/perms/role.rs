@@ -9,9 +9,27 @@ use pumpkin::{
 };
 use pumpkin_util::text::TextComponent;
 
-use crate::{permissions, utils::success_colour, get_runtime};
+use super::{describe_contexts, grant_options, refuse, unknown_role_message};
+use crate::{permissions::{self, authority::Actor}, utils::{self, success_colour, error_colour}, get_runtime};
 
-pub struct PermsRoleCommand;
+/// Whether `/perms role` adds the role to the player or removes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleAction {
+    Add,
+    Remove,
+}
+
+impl RoleAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoleAction::Add => "add",
+            RoleAction::Remove => "remove",
+        }
+    }
+}
+
+/// Runs `/perms role add` or `/perms role remove`, depending on the action.
+pub struct PermsRoleCommand(pub RoleAction);
 
 #[async_trait]
 impl CommandExecutor for PermsRoleCommand {
@@ -21,9 +39,6 @@ impl CommandExecutor for PermsRoleCommand {
         _server: &Server,
         args: &ConsumedArgs<'a>,
     ) -> Result<(), CommandError> {
-        let Some(Arg::Simple(role_action)) = args.get("role_action") else {
-            return Err(CommandError::InvalidConsumption(Some("role_action".into())));
-        };
         let Some(Arg::Players(targets)) = args.get("player") else {
             return Err(CommandError::InvalidConsumption(Some("player".into())));
         };
@@ -31,28 +46,102 @@ impl CommandExecutor for PermsRoleCommand {
             return Err(CommandError::InvalidConsumption(Some("role".into())));
         };
 
+        let (duration, contexts) = match grant_options(args) {
+            Ok(options) => options,
+            Err(message) => {
+                sender
+                    .send_message(TextComponent::text(message).color_rgb(error_colour()))
+                    .await;
+                return Ok(());
+            }
+        };
+
         let player = &targets[0];
         let player_uuid = player.gameprofile.id;
         let role_name = role.to_string();
+        let expires_at = duration.map(|seconds| utils::unix_now() + seconds);
+        let scope = describe_contexts(&contexts);
+
+        let actor = Actor::from_sender(sender);
+        if let Err(denied) = actor
+            .check_player(&player_uuid, &player.gameprofile.name)
+            .and_then(|()| actor.check_role(role))
+        {
+            refuse(sender, &actor, &player_uuid.to_string(), &format!("role {} {} for {}", self.0.as_str(), role, player.gameprofile.name), denied).await;
+            return Ok(());
+        }
 
         let runtime = get_runtime();
-        if *role_action == "add" {
-            if let Err(e) = runtime.spawn(async move {
-                permissions::add_player_to_role(&player_uuid, &role_name).await
-            }).await.unwrap() {
-                log::error!("Failed to add role: {}", e);
-                return Ok(());
-            }
-            sender
-                .send_message(TextComponent::text(format!(
-                    "Added role {} to {}",
-                    role, player.gameprofile.name
-                )).color_rgb(success_colour()))
-                .await;
-        } else {
-            sender
-                .send_message(TextComponent::text("Invalid role action. Use 'add' or 'remove'"))
-                .await;
+        match self.0 {
+            RoleAction::Add => {
+                // Unknown roles come back as the list of known ones, for suggestions
+                let added = match runtime.spawn(async move {
+                    if !permissions::role_exists(&role_name).await? {
+                        return Ok(Err(permissions::role_names().await?));
+                    }
+                    permissions::add_player_to_role(&actor, &player_uuid, &role_name, expires_at, &contexts).await.map(Ok)
+                }).await.unwrap() {
+                    Ok(Ok(added)) => added,
+                    Ok(Err(known)) => {
+                        sender
+                            .send_message(TextComponent::text(unknown_role_message(role, &known)).color_rgb(error_colour()))
+                            .await;
+                        return Ok(());
+                    },
+                    Err(e) => {
+                        log::error!("Failed to add role: {}", e);
+                        return Ok(());
+                    }
+                };
+
+                if !added {
+                    sender
+                        .send_message(TextComponent::text(format!(
+                            "{} already has role {}{}",
+                            player.gameprofile.name, role, scope
+                        )).color_rgb(error_colour()))
+                        .await;
+                    return Ok(());
+                }
+
+                let message = match duration {
+                    Some(seconds) => format!(
+                        "Added role {}{} to {} for {}",
+                        role, scope, player.gameprofile.name, utils::format_duration(seconds)
+                    ),
+                    None => format!("Added role {}{} to {}", role, scope, player.gameprofile.name),
+                };
+                sender
+                    .send_message(TextComponent::text(message).color_rgb(success_colour()))
+                    .await;
+            },
+            RoleAction::Remove => {
+                let removed = match runtime.spawn(async move {
+                    permissions::remove_player_from_role(&actor, &player_uuid, &role_name).await
+                }).await.unwrap() {
+                    Ok(removed) => removed,
+                    Err(e) => {
+                        log::error!("Failed to remove role: {}", e);
+                        return Ok(());
+                    }
+                };
+
+                if removed {
+                    sender
+                        .send_message(TextComponent::text(format!(
+                            "Removed role {} from {}",
+                            role, player.gameprofile.name
+                        )).color_rgb(success_colour()))
+                        .await;
+                } else {
+                    sender
+                        .send_message(TextComponent::text(format!(
+                            "{} does not have role {}",
+                            player.gameprofile.name, role
+                        )).color_rgb(error_colour()))
+                        .await;
+                }
+            },
         }
         Ok(())
     }
@@ -0,0 +1,269 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{Arg, ConsumedArgs},
+        dispatcher::CommandError,
+        CommandExecutor, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+
+use super::{refuse, write_through};
+use crate::{permissions::{self, authority::Actor}, utils::{success_colour, error_colour, neutral_colour}, get_runtime};
+
+fn simple_arg<'a>(args: &ConsumedArgs<'a>, name: &str) -> Result<&'a str, CommandError> {
+    match args.get(name) {
+        Some(Arg::Simple(value)) => Ok(value),
+        _ => Err(CommandError::InvalidConsumption(Some(name.into()))),
+    }
+}
+
+async fn send_error(sender: &CommandSender<'_>, message: String) {
+    sender
+        .send_message(TextComponent::text(message).color_rgb(error_colour()))
+        .await;
+}
+
+async fn send_success(sender: &CommandSender<'_>, message: String) {
+    sender
+        .send_message(TextComponent::text(message).color_rgb(success_colour()))
+        .await;
+}
+
+/// Checks that `name` is usable for a new role, reporting why not otherwise.
+async fn check_new_role_name(sender: &CommandSender<'_>, name: &str) -> bool {
+    if !permissions::is_valid_role_name(name) {
+        send_error(sender, format!(
+            "Invalid role name '{}'. Use letters, digits, '_' or '-'",
+            name
+        )).await;
+        return false;
+    }
+
+    let name_owned = name.to_string();
+    match get_runtime().spawn(async move {
+        permissions::role_exists(&name_owned).await
+    }).await.unwrap() {
+        Ok(false) => true,
+        Ok(true) => {
+            send_error(sender, format!("Role {} already exists", name)).await;
+            false
+        },
+        Err(e) => {
+            log::error!("Failed to look up role {}: {}", name, e);
+            false
+        }
+    }
+}
+
+pub struct PermsRoleCreateCommand;
+
+#[async_trait]
+impl CommandExecutor for PermsRoleCreateCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let name = simple_arg(args, "name")?;
+        let level = simple_arg(args, "level")?;
+
+        let Ok(level) = level.parse::<i32>() else {
+            send_error(sender, format!("Invalid level '{}', expected a whole number", level)).await;
+            return Ok(());
+        };
+
+        let actor = Actor::from_sender(sender);
+        if let Err(denied) = actor.check_level(level) {
+            refuse(sender, &actor, name, &format!("creating role {} with level {}", name, level), denied).await;
+            return Ok(());
+        }
+        if !check_new_role_name(sender, name).await {
+            return Ok(());
+        }
+
+        let name_owned = name.to_string();
+        if let Err(e) = get_runtime().spawn(async move {
+            permissions::create_role(&actor, &name_owned, level).await
+        }).await.unwrap() {
+            log::error!("Failed to create role {}: {}", name, e);
+            return Ok(());
+        }
+        write_through(&[name], &[]).await;
+
+        send_success(sender, format!("Created role {} with level {}", name, level)).await;
+        Ok(())
+    }
+}
+
+pub struct PermsRoleDeleteCommand;
+
+#[async_trait]
+impl CommandExecutor for PermsRoleDeleteCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let name = simple_arg(args, "name")?;
+        let fallback = match args.get("fallback") {
+            Some(Arg::Simple(fallback)) => Some(*fallback),
+            _ => None,
+        };
+
+        let actor = Actor::from_sender(sender);
+        let authorized = actor.check_role(name).and_then(|()| match fallback {
+            Some(fallback) if fallback != "none" => actor.check_role(fallback),
+            _ => Ok(()),
+        });
+        if let Err(denied) = authorized {
+            refuse(sender, &actor, name, &format!("deleting role {}", name), denied).await;
+            return Ok(());
+        }
+
+        let name_owned = name.to_string();
+        let fallback_owned = fallback.map(str::to_string);
+        let lookup = get_runtime().spawn(async move {
+            let exists = permissions::role_exists(&name_owned).await?;
+            let members = permissions::count_role_members(&name_owned).await?;
+            let fallback_exists = match &fallback_owned {
+                Some(fallback) if fallback != "none" => permissions::role_exists(fallback).await?,
+                _ => true,
+            };
+            Ok::<_, sqlx::Error>((exists, members, fallback_exists))
+        }).await.unwrap();
+
+        let (exists, members, fallback_exists) = match lookup {
+            Ok(lookup) => lookup,
+            Err(e) => {
+                log::error!("Failed to look up role {}: {}", name, e);
+                return Ok(());
+            }
+        };
+
+        if !exists {
+            send_error(sender, format!("Role {} does not exist", name)).await;
+            return Ok(());
+        }
+        // Members would silently lose the role, so make the caller choose
+        if members > 0 && fallback.is_none() {
+            sender
+                .send_message(TextComponent::text(format!(
+                    "Role {} has {} member(s). Use /perms role delete {} <fallback> to move them to another role, or /perms role delete {} none to remove them from the role",
+                    name, members, name, name
+                )).color_rgb(neutral_colour()))
+                .await;
+            return Ok(());
+        }
+        if fallback == Some(name) {
+            send_error(sender, "The fallback role must differ from the deleted role".to_string()).await;
+            return Ok(());
+        }
+        if !fallback_exists {
+            send_error(sender, format!("Fallback role {} does not exist", fallback.unwrap_or_default())).await;
+            return Ok(());
+        }
+
+        let name_owned = name.to_string();
+        let fallback_owned = fallback.filter(|fallback| *fallback != "none").map(str::to_string);
+        let moved_to = fallback_owned.clone();
+        let affected = match get_runtime().spawn(async move {
+            permissions::delete_role(&actor, &name_owned, fallback_owned.as_deref()).await
+        }).await.unwrap() {
+            Ok(affected) => affected,
+            Err(e) => {
+                log::error!("Failed to delete role {}: {}", name, e);
+                return Ok(());
+            }
+        };
+
+        write_through(&[name], &[]).await;
+
+        let message = match moved_to {
+            Some(fallback) if affected > 0 => format!("Deleted role {} and moved {} member(s) to {}", name, affected, fallback),
+            None if affected > 0 => format!("Deleted role {} and removed it from {} member(s)", name, affected),
+            _ => format!("Deleted role {}", name),
+        };
+        send_success(sender, message).await;
+        Ok(())
+    }
+}
+
+pub struct PermsRoleRenameCommand;
+
+#[async_trait]
+impl CommandExecutor for PermsRoleRenameCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let old_name = simple_arg(args, "old")?;
+        let new_name = simple_arg(args, "new")?;
+
+        let actor = Actor::from_sender(sender);
+        if let Err(denied) = actor.check_role(old_name) {
+            refuse(sender, &actor, old_name, &format!("renaming role {}", old_name), denied).await;
+            return Ok(());
+        }
+
+        if !check_new_role_name(sender, new_name).await {
+            return Ok(());
+        }
+
+        let (old_owned, new_owned) = (old_name.to_string(), new_name.to_string());
+        match get_runtime().spawn(async move {
+            permissions::rename_role(&actor, &old_owned, &new_owned).await
+        }).await.unwrap() {
+            Ok(()) => {
+                write_through(&[old_name, new_name], &[(old_name, new_name)]).await;
+                send_success(sender, format!("Renamed role {} to {}", old_name, new_name)).await;
+            },
+            Err(sqlx::Error::RowNotFound) => send_error(sender, format!("Role {} does not exist", old_name)).await,
+            Err(e) => log::error!("Failed to rename role {}: {}", old_name, e),
+        }
+        Ok(())
+    }
+}
+
+pub struct PermsRoleCloneCommand;
+
+#[async_trait]
+impl CommandExecutor for PermsRoleCloneCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let source = simple_arg(args, "source")?;
+        let target = simple_arg(args, "target")?;
+
+        let actor = Actor::from_sender(sender);
+        if let Err(denied) = actor.check_role(source) {
+            refuse(sender, &actor, source, &format!("cloning role {}", source), denied).await;
+            return Ok(());
+        }
+
+        if !check_new_role_name(sender, target).await {
+            return Ok(());
+        }
+
+        let (source_owned, target_owned) = (source.to_string(), target.to_string());
+        match get_runtime().spawn(async move {
+            permissions::clone_role(&actor, &source_owned, &target_owned).await
+        }).await.unwrap() {
+            Ok(()) => {
+                write_through(&[target], &[]).await;
+                send_success(sender, format!("Cloned role {} into {}", source, target)).await;
+            },
+            Err(sqlx::Error::RowNotFound) => send_error(sender, format!("Role {} does not exist", source)).await,
+            Err(e) => log::error!("Failed to clone role {}: {}", source, e),
+        }
+        Ok(())
+    }
+}
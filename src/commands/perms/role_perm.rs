@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{Arg, ConsumedArgs},
+        dispatcher::CommandError,
+        CommandExecutor, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+
+use super::{describe_contexts, refuse, write_through};
+use crate::{
+    permissions::{self, authority::Actor, context::ContextSet, node::{self, PermissionNode}},
+    utils::{success_colour, error_colour, neutral_colour},
+    get_runtime,
+};
+
+pub struct PermsRolePermAddCommand;
+
+#[async_trait]
+impl CommandExecutor for PermsRolePermAddCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let Some(Arg::Simple(role)) = args.get("role") else {
+            return Err(CommandError::InvalidConsumption(Some("role".into())));
+        };
+        let Some(Arg::Simple(permission)) = args.get("permission") else {
+            return Err(CommandError::InvalidConsumption(Some("permission".into())));
+        };
+        if let Err(problem) = node::check_syntax(permission) {
+            sender
+                .send_message(TextComponent::text(format!("Invalid permission '{}': {}", permission, problem)).color_rgb(error_colour()))
+                .await;
+            return Ok(());
+        }
+
+        let contexts = match args.get("context") {
+            Some(Arg::Simple(context)) => match ContextSet::parse(context) {
+                Ok(contexts) => contexts,
+                Err(message) => {
+                    sender
+                        .send_message(TextComponent::text(message).color_rgb(error_colour()))
+                        .await;
+                    return Ok(());
+                }
+            },
+            _ => ContextSet::new(),
+        };
+
+        let actor = Actor::from_sender(sender);
+        if let Err(denied) = actor
+            .check_role(role)
+            .and_then(|()| actor.check_node(permission, &contexts))
+        {
+            refuse(sender, &actor, role, &format!("adding {} to role {}", permission, role), denied).await;
+            return Ok(());
+        }
+
+        let role_name = role.to_string();
+        let permission_node = permission.to_string();
+        let scope = describe_contexts(&contexts);
+
+        match get_runtime().spawn(async move {
+            permissions::add_role_permission(&actor, &role_name, &permission_node, &contexts).await
+        }).await.unwrap() {
+            Ok(true) => {
+                write_through(&[role], &[]).await;
+                sender
+                    .send_message(TextComponent::text(format!(
+                        "Added permission {} to role {}{}",
+                        permission, role, scope
+                    )).color_rgb(success_colour()))
+                    .await;
+            },
+            Ok(false) => {
+                sender
+                    .send_message(TextComponent::text(format!(
+                        "Role {} already has permission {}{}",
+                        role, permission, scope
+                    )).color_rgb(error_colour()))
+                    .await;
+            },
+            Err(sqlx::Error::RowNotFound) => {
+                sender
+                    .send_message(TextComponent::text(format!("Role {} does not exist", role)).color_rgb(error_colour()))
+                    .await;
+            },
+            Err(e) => log::error!("Failed to add role permission: {}", e),
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PermsRolePermRemoveCommand;
+
+#[async_trait]
+impl CommandExecutor for PermsRolePermRemoveCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let Some(Arg::Simple(role)) = args.get("role") else {
+            return Err(CommandError::InvalidConsumption(Some("role".into())));
+        };
+        let Some(Arg::Simple(permission)) = args.get("permission") else {
+            return Err(CommandError::InvalidConsumption(Some("permission".into())));
+        };
+
+        let actor = Actor::from_sender(sender);
+        if let Err(denied) = actor.check_role(role) {
+            refuse(sender, &actor, role, &format!("removing {} from role {}", permission, role), denied).await;
+            return Ok(());
+        }
+
+        let role_name = role.to_string();
+        let permission_node = permission.to_string();
+
+        let message = match get_runtime().spawn(async move {
+            permissions::remove_role_permission(&actor, &role_name, &permission_node, None).await
+        }).await.unwrap() {
+            Ok(true) => {
+                write_through(&[role], &[]).await;
+                TextComponent::text(format!("Removed permission {} from role {}", permission, role))
+                    .color_rgb(success_colour())
+            },
+            Ok(false) => TextComponent::text(format!("Role {} does not have permission {}", role, permission))
+                .color_rgb(error_colour()),
+            Err(sqlx::Error::RowNotFound) => TextComponent::text(format!("Role {} does not exist", role))
+                .color_rgb(error_colour()),
+            Err(e) => {
+                log::error!("Failed to remove role permission: {}", e);
+                return Ok(());
+            }
+        };
+        sender.send_message(message).await;
+
+        Ok(())
+    }
+}
+
+pub struct PermsRolePermListCommand;
+
+// e.g. `-worldedit.* (negated, wildcard) [world=creative]`
+fn describe_role_permission(permission: &permissions::RolePermission) -> String {
+    let node = PermissionNode::parse(&permission.node);
+    let mut marks = Vec::new();
+    if node.negated {
+        marks.push("negated");
+    }
+    if node.is_wildcard() {
+        marks.push("wildcard");
+    }
+
+    let mut description = permission.node.clone();
+    if !marks.is_empty() {
+        description.push_str(&format!(" ({})", marks.join(", ")));
+    }
+    if !permission.contexts.is_empty() {
+        description.push_str(&format!(" [{}]", permission.contexts));
+    }
+    description
+}
+
+#[async_trait]
+impl CommandExecutor for PermsRolePermListCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let Some(Arg::Simple(role)) = args.get("role") else {
+            return Err(CommandError::InvalidConsumption(Some("role".into())));
+        };
+
+        let role_name = role.to_string();
+        let role = match get_runtime().spawn(async move {
+            permissions::get_role(&role_name).await
+        }).await.unwrap() {
+            Ok(role) => role,
+            Err(sqlx::Error::RowNotFound) => {
+                sender
+                    .send_message(TextComponent::text(format!("Role {} does not exist", role)).color_rgb(error_colour()))
+                    .await;
+                return Ok(());
+            },
+            Err(e) => {
+                log::error!("Failed to get role: {}", e);
+                return Ok(());
+            }
+        };
+
+        sender
+            .send_message(TextComponent::text(format!(
+                "=== Role {} (level {}) ===",
+                role.name, role.level
+            )).color_rgb(success_colour()))
+            .await;
+
+        if !role.parents.is_empty() {
+            sender
+                .send_message(TextComponent::text(format!("Inherits: {}", role.parents.join(", "))).color_rgb(neutral_colour()))
+                .await;
+        }
+
+        if role.permissions.is_empty() {
+            sender
+                .send_message(TextComponent::text("Permissions: None").color_rgb(neutral_colour()))
+                .await;
+            return Ok(());
+        }
+
+        // One line per namespace, nodes in the order they were added
+        let mut namespaces: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for permission in &role.permissions {
+            namespaces
+                .entry(PermissionNode::parse(&permission.node).namespace())
+                .or_default()
+                .push(describe_role_permission(permission));
+        }
+
+        for (namespace, nodes) in namespaces {
+            sender
+                .send_message(TextComponent::text(format!("{}: {}", namespace, nodes.join(", "))).color_rgb(neutral_colour()))
+                .await;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{Arg, ConsumedArgs},
+        dispatcher::CommandError,
+        CommandExecutor, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+
+use super::audit_log::{display_name, resolve_name};
+use super::{refuse, write_through};
+use crate::{
+    permissions::{audit::{self, AuditEntry}, authority::Actor, rollback::{self, Inverse, Step}},
+    utils::{self, success_colour, error_colour, neutral_colour},
+    get_runtime,
+};
+
+// Longer previews are cut short so they do not flood the chat
+const PREVIEW_LIMIT: usize = 20;
+
+/// Lists the steps of a rollback or undo without applying anything.
+async fn preview(sender: &CommandSender<'_>, server: &Server, steps: &[Step], confirm_command: &str) {
+    sender
+        .send_message(TextComponent::text(format!(
+            "Dry run: {} change(s) would be reverted, newest first",
+            steps.len()
+        )).color_rgb(success_colour()))
+        .await;
+
+    for step in steps.iter().take(PREVIEW_LIMIT) {
+        sender
+            .send_message(TextComponent::text(format!(
+                "#{} {} {}: {}",
+                step.entry.id,
+                step.entry.action,
+                display_name(server, &step.entry.target).await,
+                step.inverse
+            )).color_rgb(neutral_colour()))
+            .await;
+    }
+    if steps.len() > PREVIEW_LIMIT {
+        sender
+            .send_message(TextComponent::text(format!("... and {} more", steps.len() - PREVIEW_LIMIT)).color_rgb(neutral_colour()))
+            .await;
+    }
+
+    sender
+        .send_message(TextComponent::text(format!("Run {} to apply", confirm_command)).color_rgb(neutral_colour()))
+        .await;
+}
+
+/// Plans and, when `confirm` is set, applies the revert of `entries`.
+async fn revert(
+    sender: &CommandSender<'_>,
+    server: &Server,
+    entries: Vec<AuditEntry>,
+    confirm: bool,
+    confirm_command: &str,
+) {
+    if entries.is_empty() {
+        sender
+            .send_message(TextComponent::text("No changes to revert").color_rgb(neutral_colour()))
+            .await;
+        return;
+    }
+
+    let steps = match rollback::plan(entries) {
+        Ok(steps) => steps,
+        Err(e) => {
+            sender
+                .send_message(TextComponent::text(e.to_string()).color_rgb(error_colour()))
+                .await;
+            return;
+        }
+    };
+
+    let actor = Actor::from_sender(sender);
+    for step in &steps {
+        if let Err(denied) = step.inverse.authorize(&actor) {
+            refuse(sender, &actor, &step.entry.target, &format!("undoing #{}", step.entry.id), denied).await;
+            return;
+        }
+    }
+
+    if !confirm {
+        preview(sender, server, &steps, confirm_command).await;
+        return;
+    }
+
+    // Steps revert newest first, so renames are listed in the order they happen
+    let mut touched: Vec<String> = Vec::new();
+    let mut renamed: Vec<(String, String)> = Vec::new();
+    for step in &steps {
+        for role in step.inverse.roles() {
+            if !touched.iter().any(|touched| touched == role) {
+                touched.push(role.to_string());
+            }
+        }
+        if let Inverse::RenameRole { from, to } = &step.inverse {
+            renamed.push((from.clone(), to.clone()));
+        }
+    }
+
+    match get_runtime().spawn(async move {
+        rollback::apply(&actor, &steps).await
+    }).await.unwrap() {
+        Ok(reverted) => {
+            let touched: Vec<&str> = touched.iter().map(String::as_str).collect();
+            let renamed: Vec<(&str, &str)> = renamed.iter().map(|(from, to)| (from.as_str(), to.as_str())).collect();
+            write_through(&touched, &renamed).await;
+            sender
+                .send_message(TextComponent::text(format!("Reverted {} change(s)", reverted)).color_rgb(success_colour()))
+                .await;
+        },
+        Err(e) => {
+            log::error!("Failed to revert changes: {}", e);
+            sender
+                .send_message(TextComponent::text(format!("Nothing was reverted. {}", e)).color_rgb(error_colour()))
+                .await;
+        }
+    }
+}
+
+/// `/perms rollback <actor> <since> [confirm]`
+pub struct PermsRollbackCommand {
+    pub confirm: bool,
+}
+
+#[async_trait]
+impl CommandExecutor for PermsRollbackCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let Some(Arg::Simple(actor_name)) = args.get("actor") else {
+            return Err(CommandError::InvalidConsumption(Some("actor".into())));
+        };
+        let Some(Arg::Simple(since)) = args.get("since") else {
+            return Err(CommandError::InvalidConsumption(Some("since".into())));
+        };
+
+        let Some(seconds) = utils::parse_duration(since) else {
+            sender
+                .send_message(TextComponent::text(format!(
+                    "Invalid duration '{}'. Use e.g. 30m, 12h or 7d, up to 100 years", since
+                )).color_rgb(error_colour()))
+                .await;
+            return Ok(());
+        };
+
+        let actor = resolve_name(server, actor_name).await;
+        let since_time = utils::unix_now() - seconds;
+        let entries = match get_runtime().spawn(async move {
+            audit::changes_since(&actor, since_time).await
+        }).await.unwrap() {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::error!("Failed to read audit log: {}", e);
+                return Ok(());
+            }
+        };
+
+        let confirm_command = format!("/perms rollback {} {} confirm", actor_name, since);
+        revert(sender, server, entries, self.confirm, &confirm_command).await;
+        Ok(())
+    }
+}
+
+/// `/perms undo <entry-id> [confirm]`
+pub struct PermsUndoCommand {
+    pub confirm: bool,
+}
+
+#[async_trait]
+impl CommandExecutor for PermsUndoCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let Some(Arg::Simple(entry_id)) = args.get("entry") else {
+            return Err(CommandError::InvalidConsumption(Some("entry".into())));
+        };
+
+        let Ok(id) = entry_id.trim_start_matches('#').parse::<i64>() else {
+            sender
+                .send_message(TextComponent::text(format!("Invalid entry id '{}'", entry_id)).color_rgb(error_colour()))
+                .await;
+            return Ok(());
+        };
+
+        let entry = match get_runtime().spawn(async move {
+            audit::get_entry(id).await
+        }).await.unwrap() {
+            Ok(Some(entry)) => entry,
+            Ok(None) => {
+                sender
+                    .send_message(TextComponent::text(format!("Audit entry #{} does not exist", id)).color_rgb(error_colour()))
+                    .await;
+                return Ok(());
+            },
+            Err(e) => {
+                log::error!("Failed to read audit log: {}", e);
+                return Ok(());
+            }
+        };
+
+        let confirm_command = format!("/perms undo {} confirm", id);
+        revert(sender, server, vec![entry], self.confirm, &confirm_command).await;
+        Ok(())
+    }
+}
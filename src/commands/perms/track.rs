@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{Arg, ConsumedArgs},
+        dispatcher::CommandError,
+        CommandExecutor, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+
+use super::refuse;
+use crate::{
+    config,
+    permissions::{self, authority::Actor, track::{self, TrackDirection, TrackMove}},
+    utils::{success_colour, error_colour},
+    get_runtime,
+};
+
+/// Runs `/perms promote` or `/perms demote`, depending on the direction.
+pub struct PermsTrackCommand(pub TrackDirection);
+
+#[async_trait]
+impl CommandExecutor for PermsTrackCommand {
+    async fn execute<'a>(
+        &self,
+        sender: &mut CommandSender<'a>,
+        _server: &Server,
+        args: &ConsumedArgs<'a>,
+    ) -> Result<(), CommandError> {
+        let Some(Arg::Players(targets)) = args.get("player") else {
+            return Err(CommandError::InvalidConsumption(Some("player".into())));
+        };
+        let Some(Arg::Simple(track_name)) = args.get("track") else {
+            return Err(CommandError::InvalidConsumption(Some("track".into())));
+        };
+
+        let Some(track) = config::get_config().await.value.tracks.get(*track_name).cloned() else {
+            sender
+                .send_message(TextComponent::text(format!("Track {} does not exist", track_name)).color_rgb(error_colour()))
+                .await;
+            return Ok(());
+        };
+
+        let player = &targets[0];
+        let player_name = &player.gameprofile.name;
+        let player_uuid = player.gameprofile.id;
+        let direction = self.0;
+
+        let actor = Actor::from_sender(sender);
+        if let Err(denied) = actor.check_player(&player_uuid, player_name) {
+            refuse(sender, &actor, &player_uuid.to_string(), &format!("moving {} on track {}", player_name, track_name), denied).await;
+            return Ok(());
+        }
+
+        let step = match get_runtime().spawn(async move {
+            permissions::get_player_permissions(&player_uuid).await
+        }).await.unwrap() {
+            Ok(held) => track::plan_move(&held, &track, direction),
+            Err(e) => {
+                log::error!("Failed to get player permissions: {}", e);
+                return Ok(());
+            }
+        };
+
+        if let TrackMove::Moved { from, to } = &step {
+            let authorized = actor.check_role(to).and_then(|()| match from {
+                Some(from) => actor.check_role(from),
+                None => Ok(()),
+            });
+            if let Err(denied) = authorized {
+                refuse(sender, &actor, &player_uuid.to_string(), &format!("moving {} to {}", player_name, to), denied).await;
+                return Ok(());
+            }
+
+            let applied = step.clone();
+            let track_owned = track_name.to_string();
+            match get_runtime().spawn(async move {
+                track::apply_move(&actor, &player_uuid, &track_owned, &applied).await
+            }).await.unwrap() {
+                Ok(true) => {},
+                Ok(false) => {
+                    sender
+                        .send_message(TextComponent::text(format!(
+                            "{} already has role {}, so nothing was moved", player_name, to
+                        )).color_rgb(error_colour()))
+                        .await;
+                    return Ok(());
+                },
+                Err(e) => {
+                    log::error!("Failed to move player on track {}: {}", track_name, e);
+                    return Ok(());
+                }
+            }
+        }
+
+        let message = match (step, direction) {
+            (TrackMove::Moved { from: Some(from), to }, TrackDirection::Promote) => TextComponent::text(format!(
+                "Promoted {} from {} to {} on track {}", player_name, from, to, track_name
+            )).color_rgb(success_colour()),
+            (TrackMove::Moved { from: None, to }, _) => TextComponent::text(format!(
+                "Promoted {} to {} on track {}", player_name, to, track_name
+            )).color_rgb(success_colour()),
+            (TrackMove::Moved { from: Some(from), to }, TrackDirection::Demote) => TextComponent::text(format!(
+                "Demoted {} from {} to {} on track {}", player_name, from, to, track_name
+            )).color_rgb(success_colour()),
+            (TrackMove::AtTop(role), _) => TextComponent::text(format!(
+                "{} is already at the top of track {} ({})", player_name, track_name, role
+            )).color_rgb(error_colour()),
+            (TrackMove::AtBottom(role), _) => TextComponent::text(format!(
+                "{} is already at the bottom of track {} ({})", player_name, track_name, role
+            )).color_rgb(error_colour()),
+            (TrackMove::NotOnTrack, _) => TextComponent::text(format!(
+                "{} has no role on track {}", player_name, track_name
+            )).color_rgb(error_colour()),
+        };
+        sender.send_message(message).await;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+
+use crate::permissions::{self, context::ContextSet, reconcile};
+use crate::get_runtime;
+use validate::Diagnostic;
+
+pub mod persist;
+pub mod validate;
+
+// How often the watcher checks config.toml for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Vanilla op level that passes the /perms checks when `op_level` is not set.
+pub const DEFAULT_OP_LEVEL: u8 = 4;
+
+fn default_op_level() -> u8 {
+    DEFAULT_OP_LEVEL
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoleConfig {
+    pub level: i32,
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub inherits: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scoped: Vec<ScopedPermissions>,
+}
+
+/// Role permissions that only apply within `context`, written as
+/// `[[roles.<name>.scoped]]` tables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScopedPermissions {
+    pub context: ContextSet,
+    pub permissions: Vec<String>,
+}
+
+/// Which side wins when config.toml and the database disagree about a role.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconcileMode {
+    /// The database mirrors the file; runtime edits are undone on restart.
+    Config,
+    /// The file only seeds roles missing from the database.
+    Database,
+    /// Config levels and nodes are applied, runtime additions are kept.
+    #[default]
+    Merge,
+}
+
+impl ReconcileMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReconcileMode::Config => "config",
+            ReconcileMode::Database => "database",
+            ReconcileMode::Merge => "merge",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigValue {
+    #[serde(default)]
+    pub reconcile: ReconcileMode,
+    /// Reload automatically when config.toml changes on disk.
+    #[serde(default)]
+    pub watch: bool,
+    /// Write roles changed through commands back to config.toml.
+    #[serde(default)]
+    pub write_through: bool,
+    /// Vanilla op level (0-4) that may use every /perms subcommand without
+    /// holding its `hysterion.perms` node.
+    #[serde(default = "default_op_level")]
+    pub op_level: u8,
+    pub roles: HashMap<String, RoleConfig>,
+    /// Promotion tracks, each listing role names from lowest to highest.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tracks: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub value: ConfigValue,
+    path: String,
+}
+
+impl Config {
+    /// Path of the config.toml this config was read from.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// Why config.toml was not applied: every problem found in it, or a failure
+/// to read or apply it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError(pub Vec<Diagnostic>);
+
+impl ConfigError {
+    pub fn new(message: impl Into<String>) -> Self {
+        ConfigError(vec![Diagnostic::error(message)])
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let problems: Vec<String> = self.0.iter().map(Diagnostic::to_string).collect();
+        write!(f, "{}", problems.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Parses and validates the contents of config.toml. Warnings are logged;
+/// any error fails the load with every problem found.
+pub fn parse(content: &str) -> Result<ConfigValue, ConfigError> {
+    let report = validate::check(content);
+    if report.has_errors() {
+        return Err(ConfigError(report.diagnostics));
+    }
+    for diagnostic in &report.diagnostics {
+        log::warn!("[HysterionPerms] config.toml {}", diagnostic);
+    }
+    report.value.ok_or_else(|| ConfigError::new("config.toml could not be read"))
+}
+
+static CONFIG_INSTANCE: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+
+// Keeps the reload command and the watcher from applying a file at the same time
+static RELOAD_LOCK: Mutex<()> = Mutex::const_new(());
+
+impl Config {
+    #[allow(dead_code)]
+    pub async fn init(path: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let config_path = format!("{}/config.toml", path);
+        
+        if Path::new(&config_path).exists() {
+            let content = tokio::fs::read_to_string(&config_path).await?;
+            let value = parse(&content)?;
+            Ok(Config {
+                value,
+                path: config_path,
+            })
+        } else {
+            Ok(Config::new(&config_path).await)
+        }
+    }
+
+    #[allow(dead_code)]
+    pub async fn new(path: &str) -> Self {
+        let default_config = r#"
+[roles.default]
+level = 0
+permissions = []
+
+[roles.admin]
+level = 4
+permissions = ["*"]
+"#;
+
+        tokio::fs::write(path, default_config).await.unwrap();
+
+        let value: ConfigValue = toml::from_str(default_config).unwrap();
+
+        Config {
+            value,
+            path: path.to_owned(),
+        }
+    }
+
+    /// Writes the roles and tracks of this config to its file. The file is
+    /// edited in place, so comments, key order and untouched entries are kept.
+    pub async fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut document: toml_edit::DocumentMut = content.parse()?;
+        persist::sync_roles(&mut document, &self.value.roles);
+        persist::sync_tracks(&mut document, &self.value.tracks);
+        tokio::fs::write(&self.path, document.to_string()).await?;
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+pub async fn setup_config(path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Config::init(path).await?;
+    let mut instance = CONFIG_INSTANCE.write().unwrap();
+    if instance.is_some() {
+        return Err("Failed to set Config instance: already initialized".into());
+    }
+    *instance = Some(Arc::new(config));
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub async fn get_config() -> Arc<Config> {
+    CONFIG_INSTANCE.read().unwrap().clone().expect("Config not initialized")
+}
+
+/// The active config, read without waiting, for callers that cannot await
+/// such as command requirements. `None` before setup.
+pub fn current() -> Option<Arc<Config>> {
+    CONFIG_INSTANCE.read().unwrap().clone()
+}
+
+/// The configured op level fallback.
+pub fn op_level() -> u8 {
+    current().map_or(DEFAULT_OP_LEVEL, |config| config.value.op_level)
+}
+
+/// Re-reads config.toml, applies its roles to the database and makes it the
+/// active config. On any error the previous config stays active.
+pub async fn reload() -> Result<Arc<Config>, ConfigError> {
+    let _guard = RELOAD_LOCK.lock().await;
+    let path = get_config().await.path.clone();
+
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| ConfigError::new(format!("Failed to read {}: {}", path, e)))?;
+    let value = parse(&content)?;
+    reconcile::apply(&value).await.map_err(ConfigError::new)?;
+
+    let config = Arc::new(Config { value, path });
+    *CONFIG_INSTANCE.write().unwrap() = Some(config.clone());
+    Ok(config)
+}
+
+/// Copies the stored state of `roles` into config.toml when write-through is
+/// enabled; roles no longer in the database are removed from the file and
+/// its tracks, and roles inheriting from `roles` are refreshed with them.
+/// `renamed` lists the old and new names of renamed roles, in the order the
+/// renames happened, so tracks can list them under their new names. Runs
+/// database queries, so it is called from within the plugin runtime.
+pub async fn write_through(
+    roles: &[&str],
+    renamed: &[(&str, &str)],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let _guard = RELOAD_LOCK.lock().await;
+    let current = get_config().await;
+    if !current.value.write_through {
+        return Ok(());
+    }
+
+    // Renames and deletions also change the parents of the roles below
+    let mut names: Vec<&str> = roles.to_vec();
+    for (name, role) in &current.value.roles {
+        if role.inherits.iter().any(|parent| roles.contains(&parent.as_str())) && !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+
+    let mut value = current.value.clone();
+    let mut removed: Vec<&str> = Vec::new();
+    for name in &names {
+        match permissions::get_role(name).await {
+            Ok(role) => {
+                value.roles.insert(role.name.clone(), persist::role_config(&role));
+            },
+            Err(sqlx::Error::RowNotFound) => {
+                value.roles.remove(*name);
+                removed.push(name);
+            },
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    for track in value.tracks.values_mut() {
+        for (old, new) in renamed {
+            for role in track.iter_mut().filter(|role| role == old) {
+                *role = new.to_string();
+            }
+        }
+        track.retain(|role| !removed.contains(&role.as_str()));
+    }
+
+    let config = Config { value, path: current.path.clone() };
+    config.save().await?;
+    *CONFIG_INSTANCE.write().unwrap() = Some(Arc::new(config));
+    Ok(())
+}
+
+async fn modified(path: &str) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+/// Polls config.toml every [`WATCH_INTERVAL`] and reloads it when it changes,
+/// as long as the active config has `watch` enabled.
+pub fn spawn_watcher() {
+    get_runtime().spawn(async move {
+        let path = get_config().await.path.clone();
+        let mut last_modified = modified(&path).await;
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+            let current = modified(&path).await;
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            if !get_config().await.value.watch {
+                continue;
+            }
+
+            match reload().await {
+                Ok(config) => log::info!("[HysterionPerms] Reloaded {} ({} role(s))", path, config.value.roles.len()),
+                Err(e) => log::error!("[HysterionPerms] Kept the previous config, {} could not be applied: {}", path, e),
+            }
+        }
+    });
+} 
\ No newline at end of file
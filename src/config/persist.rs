@@ -0,0 +1,447 @@
+// Writing roles back into config.toml without losing its formatting. Entries
+// are edited in place: values that did not change keep their comments, kept
+// nodes keep their position, and new roles are appended after the others.
+use std::collections::HashMap;
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, InlineTable, Item, RawString, Table, TableLike, Value};
+
+use super::{RoleConfig, ScopedPermissions};
+use crate::permissions::{context::ContextSet, Role};
+
+/// The config entry describing a stored role.
+pub fn role_config(role: &Role) -> RoleConfig {
+    let mut permissions = Vec::new();
+    let mut scoped: Vec<ScopedPermissions> = Vec::new();
+    for permission in &role.permissions {
+        if permission.contexts.is_empty() {
+            permissions.push(permission.node.clone());
+            continue;
+        }
+        match scoped.iter_mut().find(|group| group.context == permission.contexts) {
+            Some(group) => group.permissions.push(permission.node.clone()),
+            None => scoped.push(ScopedPermissions {
+                context: permission.contexts.clone(),
+                permissions: vec![permission.node.clone()],
+            }),
+        }
+    }
+
+    let mut inherits = role.parents.clone();
+    inherits.sort();
+    RoleConfig { level: role.level, permissions, inherits, scoped }
+}
+
+/// Makes the `[roles]` of `document` match `roles`, adding, editing and
+/// removing entries as needed.
+pub fn sync_roles(document: &mut DocumentMut, roles: &HashMap<String, RoleConfig>) {
+    if !document.contains_key("roles") {
+        let mut table = Table::new();
+        table.set_implicit(true);
+        document.insert("roles", Item::Table(table));
+    }
+    let Some(table) = document.get_mut("roles").and_then(Item::as_table_like_mut) else {
+        return;
+    };
+
+    let stale: Vec<String> = table.iter().map(|(name, _)| name.to_string()).filter(|name| !roles.contains_key(name)).collect();
+    for name in stale {
+        table.remove(&name);
+    }
+
+    let mut names: Vec<&String> = roles.keys().collect();
+    names.sort();
+    for name in names {
+        let role = &roles[name];
+        match table.get_mut(name).and_then(Item::as_table_like_mut) {
+            Some(entry) => sync_role(entry, role),
+            None => {
+                let mut entry = Table::new();
+                sync_role(&mut entry, role);
+                table.insert(name, Item::Table(entry));
+            }
+        }
+    }
+}
+
+/// Makes the `[tracks]` of `document` match `tracks`. Roles keep their
+/// place in a track, so a renamed role is replaced where it stands.
+pub fn sync_tracks(document: &mut DocumentMut, tracks: &HashMap<String, Vec<String>>) {
+    if !document.contains_key("tracks") {
+        if tracks.is_empty() {
+            return;
+        }
+        document.insert("tracks", Item::Table(Table::new()));
+    }
+    let Some(table) = document.get_mut("tracks").and_then(Item::as_table_like_mut) else {
+        return;
+    };
+
+    let stale: Vec<String> = table.iter().map(|(name, _)| name.to_string()).filter(|name| !tracks.contains_key(name)).collect();
+    for name in stale {
+        table.remove(&name);
+    }
+
+    let mut names: Vec<&String> = tracks.keys().collect();
+    names.sort();
+    for name in names {
+        let wanted = &tracks[name];
+        let Some(array) = table.get_mut(name).and_then(Item::as_array_mut) else {
+            table.insert(name, value(wanted.iter().map(String::as_str).collect::<Array>()));
+            continue;
+        };
+
+        if array.len() == wanted.len() {
+            for (element, role) in array.iter_mut().zip(wanted) {
+                if element.as_str() != Some(role) {
+                    set_keeping_decor(element, Value::from(role.as_str()));
+                }
+            }
+            continue;
+        }
+        for index in (0..array.len()).rev() {
+            let keep = array.get(index).and_then(Value::as_str).is_some_and(|role| wanted.iter().any(|wanted| wanted == role));
+            if !keep {
+                remove_element(array, index);
+            }
+        }
+        for role in wanted {
+            if !array.iter().any(|element| element.as_str() == Some(role)) {
+                push_element(array, role);
+            }
+        }
+    }
+}
+
+fn sync_role(entry: &mut dyn TableLike, role: &RoleConfig) {
+    let level = i64::from(role.level);
+    match entry.get_mut("level").and_then(Item::as_value_mut) {
+        Some(old) if old.as_integer() == Some(level) => {},
+        Some(old) => set_keeping_decor(old, Value::from(level)),
+        None => {
+            entry.insert("level", value(level));
+        }
+    }
+
+    sync_array(entry, "permissions", &role.permissions, true);
+    sync_array(entry, "inherits", &role.inherits, false);
+    sync_scoped(entry, &role.scoped);
+}
+
+// Keeps the elements still wanted, in place with their comments, and appends
+// the rest formatted like the existing ones
+fn sync_array(entry: &mut dyn TableLike, key: &str, wanted: &[String], required: bool) {
+    let Some(array) = entry.get_mut(key).and_then(Item::as_array_mut) else {
+        if required || !wanted.is_empty() {
+            entry.insert(key, value(wanted.iter().map(String::as_str).collect::<Array>()));
+        }
+        return;
+    };
+
+    for index in (0..array.len()).rev() {
+        let keep = array.get(index).and_then(Value::as_str).is_some_and(|node| wanted.iter().any(|wanted| wanted == node));
+        if !keep {
+            remove_element(array, index);
+        }
+    }
+    for node in wanted {
+        if !array.iter().any(|element| element.as_str() == Some(node)) {
+            push_element(array, node);
+        }
+    }
+}
+
+// A comment after an element's comma is stored before the next element, so
+// it is handed on rather than dropped with the element it follows
+fn remove_element(array: &mut Array, index: usize) {
+    let removed = array.remove(index);
+    let removed_prefix = decor_str(removed.decor().prefix());
+    let comment = comment_part(removed_prefix);
+    if let Some(next) = array.get_mut(index) {
+        let next_prefix = decor_str(next.decor().prefix());
+        let prefix = match next_prefix.find('\n') {
+            // Up to its first line break, the next prefix is the rest of the
+            // removed element's line, including any comment about it
+            Some(start) => format!("{}{}", comment, &next_prefix[start..]),
+            // A new first element takes over the spacing after `[`
+            None if index == 0 => removed_prefix.to_string(),
+            None if comment.is_empty() => next_prefix.to_string(),
+            None => format!("{}{}", comment, closing_part(removed_prefix)),
+        };
+        next.decor_mut().set_prefix(prefix);
+        return;
+    }
+
+    // The last element also holds the line break before `]`
+    let closing = closing_part(decor_str(removed.decor().suffix()));
+    match array.iter_mut().last() {
+        Some(last) => {
+            let suffix = format!("{}{}{}", decor_str(last.decor().suffix()), comment, closing);
+            last.decor_mut().set_suffix(suffix);
+        },
+        None if comment.is_empty() => array.set_trailing(""),
+        None => array.set_trailing(format!("{}{}", comment, closing)),
+    }
+}
+
+fn push_element(array: &mut Array, node: &str) {
+    let mut element = Value::from(node);
+    let trailing_comma = array.trailing_comma();
+    if let Some(last) = array.iter_mut().last() {
+        // Same line break and indent as the last element, without its comments
+        let last_prefix = decor_str(last.decor().prefix());
+        let mut prefix = match closing_part(last_prefix) {
+            "" if last_prefix.is_empty() => " ".to_string(),
+            "" => last_prefix.to_string(),
+            indent => indent.to_string(),
+        };
+        if !trailing_comma {
+            // Whatever followed the old last element now follows the new one,
+            // except a comment, which stays on its line after the new comma
+            let suffix = decor_str(last.decor().suffix()).to_string();
+            let closing = closing_part(&suffix);
+            let comment = &suffix[..suffix.len() - closing.len()];
+            last.decor_mut().set_suffix("");
+            if !comment.trim().is_empty() {
+                prefix = format!("{}{}", comment, prefix);
+            }
+            element.decor_mut().set_suffix(closing.to_string());
+        }
+        element.decor_mut().set_prefix(prefix);
+    }
+    array.push_formatted(element);
+}
+
+// Replaces a value but keeps the spacing and comments around it
+fn set_keeping_decor(old: &mut Value, new: Value) {
+    let decor = old.decor().clone();
+    *old = new;
+    *old.decor_mut() = decor;
+}
+
+fn decor_str(raw: Option<&RawString>) -> &str {
+    raw.and_then(RawString::as_str).unwrap_or_default()
+}
+
+// Everything before the last line break, when it holds a comment
+fn comment_part(decor: &str) -> &str {
+    match decor.rfind('\n') {
+        Some(end) if decor[..end].contains('#') => &decor[..end],
+        _ => "",
+    }
+}
+
+// From the last line break on: the indent of an element, or the whitespace
+// in front of `]`
+fn closing_part(decor: &str) -> &str {
+    decor.rfind('\n').map_or("", |start| &decor[start..])
+}
+
+fn sync_scoped(entry: &mut dyn TableLike, wanted: &[ScopedPermissions]) {
+    if wanted.is_empty() {
+        entry.remove("scoped");
+        return;
+    }
+    if entry.get("scoped").and_then(Item::as_array_of_tables).is_none() {
+        entry.insert("scoped", Item::ArrayOfTables(ArrayOfTables::new()));
+    }
+    let Some(tables) = entry.get_mut("scoped").and_then(Item::as_array_of_tables_mut) else {
+        return;
+    };
+
+    tables.retain(|table| {
+        let contexts = table_contexts(table);
+        wanted.iter().any(|group| Some(&group.context) == contexts.as_ref())
+    });
+    for group in wanted {
+        let existing = tables
+            .iter_mut()
+            .find(|table| table_contexts(table).as_ref() == Some(&group.context));
+        match existing {
+            Some(table) => sync_array(table, "permissions", &group.permissions, true),
+            None => {
+                let mut context = InlineTable::new();
+                for (key, value) in group.context.iter() {
+                    context.insert(key, Value::from(value));
+                }
+                let mut table = Table::new();
+                table.insert("context", value(context));
+                table.insert("permissions", value(group.permissions.iter().map(String::as_str).collect::<Array>()));
+                tables.push(table);
+            }
+        }
+    }
+}
+
+// Contexts of a `[[roles.<name>.scoped]]` table as written in the file
+fn table_contexts(table: &Table) -> Option<ContextSet> {
+    let context = table.get("context")?.as_table_like()?;
+    let mut contexts = ContextSet::new();
+    for (key, item) in context.iter() {
+        contexts.insert(key, item.as_str()?);
+    }
+    Some(contexts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(level: i32, permissions: &[&str], inherits: &[&str]) -> RoleConfig {
+        RoleConfig {
+            level,
+            permissions: permissions.iter().map(|node| node.to_string()).collect(),
+            inherits: inherits.iter().map(|name| name.to_string()).collect(),
+            scoped: Vec::new(),
+        }
+    }
+
+    fn synced(content: &str, roles: &[(&str, RoleConfig)]) -> String {
+        let mut document: DocumentMut = content.parse().unwrap();
+        let roles = roles.iter().map(|(name, role)| (name.to_string(), role.clone())).collect();
+        sync_roles(&mut document, &roles);
+        let output = document.to_string();
+        assert!(output.parse::<DocumentMut>().is_ok(), "invalid output:\n{}", output);
+        output
+    }
+
+    const MODERATOR: &str = "\
+# Staff roles
+[roles.moderator]
+level = 50 # above helper
+permissions = [
+    \"mod.kick\",   # kicking
+    \"mod.ban\",    # banning
+    \"mod.mute\"    # muting
+]
+";
+
+    #[test]
+    fn unchanged_roles_are_left_alone() {
+        let roles = [("moderator", role(50, &["mod.kick", "mod.ban", "mod.mute"], &[]))];
+        assert_eq!(synced(MODERATOR, &roles), MODERATOR);
+    }
+
+    #[test]
+    fn removing_a_node_keeps_the_other_comments() {
+        let roles = [("moderator", role(50, &["mod.kick", "mod.mute"], &[]))];
+        assert_eq!(
+            synced(MODERATOR, &roles),
+            "\
+# Staff roles
+[roles.moderator]
+level = 50 # above helper
+permissions = [
+    \"mod.kick\",   # kicking
+    \"mod.mute\"    # muting
+]
+"
+        );
+    }
+
+    #[test]
+    fn removing_the_last_node_keeps_the_closing_bracket_in_place() {
+        let roles = [("moderator", role(50, &["mod.kick", "mod.ban"], &[]))];
+        assert_eq!(
+            synced(MODERATOR, &roles),
+            "\
+# Staff roles
+[roles.moderator]
+level = 50 # above helper
+permissions = [
+    \"mod.kick\",   # kicking
+    \"mod.ban\"    # banning
+]
+"
+        );
+    }
+
+    #[test]
+    fn removing_the_first_node_keeps_comments_above_the_next() {
+        let content = "[roles.a]\nlevel = 1\npermissions = [ # nodes\n    \"x\", # gone\n    # kept\n    \"y\"\n]\n";
+        let roles = [("a", role(1, &["y"], &[]))];
+        assert_eq!(synced(content, &roles), "[roles.a]\nlevel = 1\npermissions = [ # nodes\n    # kept\n    \"y\"\n]\n");
+    }
+
+    #[test]
+    fn appended_nodes_follow_the_existing_layout() {
+        let roles = [("moderator", role(60, &["mod.kick", "mod.ban", "mod.mute", "mod.warn"], &[]))];
+        assert_eq!(
+            synced(MODERATOR, &roles),
+            "\
+# Staff roles
+[roles.moderator]
+level = 60 # above helper
+permissions = [
+    \"mod.kick\",   # kicking
+    \"mod.ban\",    # banning
+    \"mod.mute\",    # muting
+    \"mod.warn\"
+]
+"
+        );
+    }
+
+    #[test]
+    fn single_line_arrays_stay_on_one_line() {
+        let content = "[roles.helper]\nlevel = 10\npermissions = [\"a\", \"b\"]\ninherits = [\"default\"]\n";
+        let roles = [("helper", role(10, &["b", "c"], &[]))];
+        assert_eq!(synced(content, &roles), "[roles.helper]\nlevel = 10\npermissions = [\"b\", \"c\"]\ninherits = []\n");
+    }
+
+    const TRACKS: &str = "\
+[tracks]
+# Staff ladder
+staff = [
+    \"helper\",     # first step
+    \"moderator\",
+    \"admin\"       # top
+]
+";
+
+    fn synced_tracks(content: &str, tracks: &[(&str, &[&str])]) -> String {
+        let mut document: DocumentMut = content.parse().unwrap();
+        let tracks = tracks
+            .iter()
+            .map(|(name, roles)| (name.to_string(), roles.iter().map(|role| role.to_string()).collect()))
+            .collect();
+        sync_tracks(&mut document, &tracks);
+        document.to_string()
+    }
+
+    #[test]
+    fn renamed_track_roles_keep_their_place() {
+        assert_eq!(
+            synced_tracks(TRACKS, &[("staff", &["trainee", "moderator", "admin"])]),
+            TRACKS.replace("\"helper\"", "\"trainee\"")
+        );
+    }
+
+    #[test]
+    fn deleted_track_roles_are_dropped() {
+        assert_eq!(
+            synced_tracks(TRACKS, &[("staff", &["helper", "admin"])]),
+            "\
+[tracks]
+# Staff ladder
+staff = [
+    \"helper\",     # first step
+    \"admin\"       # top
+]
+"
+        );
+    }
+
+    #[test]
+    fn files_without_tracks_are_left_alone() {
+        assert_eq!(synced_tracks("reconcile = \"merge\"\n", &[]), "reconcile = \"merge\"\n");
+    }
+
+    #[test]
+    fn roles_are_added_and_removed() {
+        let content = "reconcile = \"merge\"\n\n[roles.old]\nlevel = 1\npermissions = []\n";
+        let roles = [("new", role(2, &["a.b"], &["default"]))];
+        assert_eq!(
+            synced(content, &roles),
+            "reconcile = \"merge\"\n\n[roles.new]\nlevel = 2\npermissions = [\"a.b\"]\ninherits = [\"default\"]\n"
+        );
+    }
+}
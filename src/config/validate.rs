@@ -0,0 +1,474 @@
+// Checks config.toml for mistakes that parsing alone lets through, reporting
+// each one with its line and column. Errors stop a file from being applied;
+// warnings are only reported.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use toml_edit::{ImDocument, Item, TableLike};
+
+use super::ConfigValue;
+use crate::permissions::{inheritance, node::{self, PermissionNode}};
+
+const TOP_LEVEL_KEYS: [&str; 6] = ["reconcile", "watch", "write_through", "op_level", "roles", "tracks"];
+const ROLE_KEYS: [&str; 4] = ["level", "permissions", "inherits", "scoped"];
+const SCOPED_KEYS: [&str; 2] = ["context", "permissions"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One problem found in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line and column.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// An error that is not tied to a place in the file.
+    pub fn error(message: impl Into<String>) -> Self {
+        Diagnostic { severity: Severity::Error, location: None, message: message.into() }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.location {
+            Some((line, column)) => write!(f, "{} at line {}, column {}: {}", label, line, column, self.message),
+            None => write!(f, "{}: {}", label, self.message),
+        }
+    }
+}
+
+/// Outcome of checking a file: the parsed config when it could be read, and
+/// every problem found, in file order.
+#[derive(Debug)]
+pub struct Report {
+    pub value: Option<ConfigValue>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+/// 1-based line and column of the byte `offset` in `content`.
+pub fn line_col(content: &str, offset: usize) -> (usize, usize) {
+    let before = content.get(..offset).unwrap_or(content);
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+// A node as written in a role, with the context set it applies in
+struct NodeEntry {
+    raw: String,
+    contexts: String,
+    span: Option<Range<usize>>,
+}
+
+struct Checker<'a> {
+    content: &'a str,
+    diagnostics: Vec<Diagnostic>,
+    /// Nodes of each role, for the checks that need inheritance.
+    nodes: HashMap<String, Vec<NodeEntry>>,
+}
+
+/// Parses and checks the contents of config.toml.
+pub fn check(content: &str) -> Report {
+    let mut checker = Checker { content, diagnostics: Vec::new(), nodes: HashMap::new() };
+
+    let document = match ImDocument::parse(content) {
+        Ok(document) => document,
+        Err(e) => {
+            checker.push(Severity::Error, e.span(), one_line(e.message()));
+            return Report { value: None, diagnostics: checker.diagnostics };
+        }
+    };
+
+    checker.check_unknown_keys(document.as_table(), &TOP_LEVEL_KEYS, "");
+    if let Some(op_level) = document.get("op_level") {
+        if op_level.as_integer().is_some_and(|level| !(0..=4).contains(&level)) {
+            checker.push(Severity::Error, op_level.span(), "op_level must be between 0 and 4");
+        }
+    }
+    if let Some(roles) = document.get("roles").and_then(Item::as_table_like) {
+        checker.check_roles(roles);
+    }
+
+    let value = match toml::from_str::<ConfigValue>(content) {
+        Ok(value) => {
+            checker.check_inheritance(&value, &document);
+            Some(value)
+        },
+        Err(e) => {
+            checker.push(Severity::Error, e.span(), one_line(e.message()));
+            None
+        }
+    };
+
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.location);
+    Report { value, diagnostics }
+}
+
+impl Checker<'_> {
+    fn push(&mut self, severity: Severity, span: Option<Range<usize>>, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            location: span.map(|span| line_col(self.content, span.start)),
+            message: message.into(),
+        });
+    }
+
+    fn check_unknown_keys(&mut self, table: &dyn TableLike, known: &[&str], path: &str) {
+        for (key, _) in table.iter() {
+            if known.contains(&key) {
+                continue;
+            }
+            let span = table.get_key_value(key).and_then(|(key, _)| key.span());
+            self.push(
+                Severity::Error,
+                span,
+                format!("unknown key '{}{}', expected one of {}", path, key, known.join(", ")),
+            );
+        }
+    }
+
+    fn check_roles(&mut self, roles: &dyn TableLike) {
+        let mut levels: HashMap<i64, String> = HashMap::new();
+
+        // File order, so duplicates are reported where they are repeated
+        let mut entries: Vec<(&str, &Item, Option<Range<usize>>)> = roles
+            .iter()
+            .map(|(name, item)| (name, item, roles.get_key_value(name).and_then(|(key, _)| key.span())))
+            .collect();
+        entries.sort_by_key(|(_, _, span)| span.as_ref().map(|span| span.start));
+
+        for (name, item, _) in entries {
+            let Some(role) = item.as_table_like() else {
+                continue;
+            };
+            self.check_unknown_keys(role, &ROLE_KEYS, &format!("roles.{}.", name));
+
+            if let Some(level_item) = role.get("level") {
+                if let Some(level) = level_item.as_integer() {
+                    match levels.get(&level) {
+                        Some(other) => self.push(
+                            Severity::Warning,
+                            level_item.span(),
+                            format!("role {} has level {}, the same as role {}", name, level, other),
+                        ),
+                        None => {
+                            levels.insert(level, name.to_string());
+                        }
+                    }
+                }
+            }
+
+            let mut nodes = Vec::new();
+            if let Some(permissions) = role.get("permissions") {
+                self.collect_nodes(permissions, String::new(), &mut nodes);
+            }
+            if let Some(scoped) = role.get("scoped").and_then(Item::as_array_of_tables) {
+                for table in scoped.iter() {
+                    self.check_unknown_keys(table, &SCOPED_KEYS, &format!("roles.{}.scoped.", name));
+                    let contexts = table.get("context").map(context_key).unwrap_or_default();
+                    if let Some(permissions) = table.get("permissions") {
+                        self.collect_nodes(permissions, contexts, &mut nodes);
+                    }
+                }
+            }
+
+            self.check_nodes(name, &nodes);
+            self.nodes.insert(name.to_string(), nodes);
+        }
+    }
+
+    // Reads the strings of a `permissions` array, reporting malformed nodes
+    fn collect_nodes(&mut self, permissions: &Item, contexts: String, nodes: &mut Vec<NodeEntry>) {
+        let Some(array) = permissions.as_array() else {
+            return;
+        };
+        for value in array.iter() {
+            let Some(raw) = value.as_str() else {
+                continue;
+            };
+            if let Err(problem) = node::check_syntax(raw) {
+                self.push(Severity::Error, value.span(), format!("malformed node '{}': {}", raw, problem));
+                continue;
+            }
+            nodes.push(NodeEntry { raw: raw.to_string(), contexts: contexts.clone(), span: value.span() });
+        }
+    }
+
+    // Repeated nodes, and explicit nodes already covered by a wildcard of the
+    // same polarity in the same contexts
+    fn check_nodes(&mut self, role: &str, nodes: &[NodeEntry]) {
+        let mut seen: HashSet<(&str, &str)> = HashSet::new();
+        for entry in nodes {
+            if !seen.insert((&entry.raw, &entry.contexts)) {
+                self.push(
+                    Severity::Warning,
+                    entry.span.clone(),
+                    format!("role {} lists {}{} more than once", role, entry.raw, describe_contexts(&entry.contexts)),
+                );
+                continue;
+            }
+
+            let explicit = PermissionNode::parse(&entry.raw);
+            if explicit.is_wildcard() {
+                continue;
+            }
+            let shadowing = nodes.iter().find(|other| {
+                let wildcard = PermissionNode::parse(&other.raw);
+                other.contexts == entry.contexts
+                    && wildcard.is_wildcard()
+                    && wildcard.negated == explicit.negated
+                    && node::pattern_matches(wildcard.pattern, explicit.pattern)
+            });
+            if let Some(wildcard) = shadowing {
+                self.push(
+                    Severity::Warning,
+                    entry.span.clone(),
+                    format!(
+                        "{} in role {} is already {} by the wildcard {}",
+                        entry.raw, role, if explicit.negated { "denied" } else { "granted" }, wildcard.raw
+                    ),
+                );
+            }
+        }
+    }
+
+    // Cycles, unknown parents and track roles, and nodes a role repeats from
+    // a role it inherits
+    fn check_inheritance(&mut self, config: &ConfigValue, document: &ImDocument<&str>) {
+        let inherits_span = |role: &str| {
+            document.get("roles")
+                .and_then(|roles| roles.get(role))
+                .and_then(|role| role.get("inherits"))
+                .and_then(Item::span)
+        };
+
+        let graph = config.roles.iter()
+            .map(|(name, role)| (name.as_str(), role.inherits.iter().map(String::as_str).collect()))
+            .collect();
+        if let Some(cycle) = inheritance::find_cycle(&graph) {
+            self.push(
+                Severity::Error,
+                inherits_span(cycle[0]),
+                format!("role inheritance cycle: {}", cycle.join(" -> ")),
+            );
+            return;
+        }
+
+        let mut names: Vec<&String> = config.roles.keys().collect();
+        names.sort();
+        for name in names {
+            for parent in &config.roles[name].inherits {
+                if !config.roles.contains_key(parent) {
+                    self.push(Severity::Warning, inherits_span(name), format!("role {} inherits unknown role {}", name, parent));
+                }
+            }
+
+            for ancestor in ancestors(config, name) {
+                let Some(inherited) = self.nodes.get(&ancestor) else {
+                    continue;
+                };
+                let repeated: Vec<(String, Option<Range<usize>>)> = self.nodes[name.as_str()]
+                    .iter()
+                    .filter(|entry| inherited.iter().any(|other| other.raw == entry.raw && other.contexts == entry.contexts))
+                    .map(|entry| (entry.raw.clone(), entry.span.clone()))
+                    .collect();
+                for (raw, span) in repeated {
+                    self.push(
+                        Severity::Warning,
+                        span,
+                        format!("{} in role {} is already inherited from role {}", raw, name, ancestor),
+                    );
+                }
+            }
+        }
+
+        let mut tracks: Vec<(&String, &Vec<String>)> = config.tracks.iter().collect();
+        tracks.sort();
+        for (track, roles) in tracks {
+            for role in roles.iter().filter(|role| !config.roles.contains_key(*role)) {
+                let span = document.get("tracks").and_then(|tracks| tracks.get(track)).and_then(Item::span);
+                self.push(Severity::Warning, span, format!("track {} lists unknown role {}", track, role));
+            }
+        }
+    }
+}
+
+// Every role `name` inherits from, directly or not
+fn ancestors(config: &ConfigValue, name: &str) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    let mut queue: Vec<&str> = vec![name];
+    while let Some(current) = queue.pop() {
+        let Some(role) = config.roles.get(current) else {
+            continue;
+        };
+        for parent in &role.inherits {
+            if parent != name && !found.contains(parent) {
+                found.push(parent.clone());
+                queue.push(parent);
+            }
+        }
+    }
+    found
+}
+
+// Canonical `key=value` form of a scoped table's `context`, for comparisons
+fn context_key(item: &Item) -> String {
+    let Some(table) = item.as_table_like() else {
+        return String::new();
+    };
+    let mut pairs: Vec<String> = table
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value.as_str().unwrap_or_default()))
+        .collect();
+    pairs.sort();
+    pairs.join(",")
+}
+
+// Parser messages can span several lines, which chat shows poorly
+fn one_line(message: &str) -> String {
+    message.trim().lines().collect::<Vec<_>>().join("; ")
+}
+
+fn describe_contexts(contexts: &str) -> String {
+    if contexts.is_empty() {
+        String::new()
+    } else {
+        format!(" in {}", contexts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Diagnostics as `(line, column, message)`, in file order
+    fn diagnostics(content: &str) -> Vec<(usize, usize, String)> {
+        check(content)
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| {
+                let (line, column) = diagnostic.location.unwrap_or_default();
+                (line, column, diagnostic.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn line_col_counts_from_one() {
+        let content = "ab\ncdé\nf";
+        assert_eq!(line_col(content, 0), (1, 1));
+        assert_eq!(line_col(content, 4), (2, 2));
+        assert_eq!(line_col(content, 8), (3, 1));
+    }
+
+    #[test]
+    fn reports_errors_where_they_are() {
+        let content = "op_level = 7\n\n[roles.admin]\nlevel = 10\npermissions = [\"a.b\", \"a..c\"]\nsorting = true\n";
+        let report = check(content);
+        assert!(report.has_errors());
+        assert_eq!(
+            diagnostics(content),
+            [
+                (1, 12, "error at line 1, column 12: op_level must be between 0 and 4".to_string()),
+                (5, 23, "error at line 5, column 23: malformed node 'a..c': node has an empty segment".to_string()),
+                (6, 1, "error at line 6, column 1: unknown key 'roles.admin.sorting', expected one of level, permissions, inherits, scoped".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_about_duplicate_and_shadowed_nodes() {
+        let content = "\
+[roles.mod]
+level = 5
+permissions = [
+    \"mod.*\",
+    \"mod.kick\",
+    \"-mod.ban\",
+    \"mod.*\",
+]
+";
+        let report = check(content);
+        assert!(!report.has_errors());
+        assert!(report.value.is_some());
+        assert_eq!(
+            diagnostics(content),
+            [
+                (5, 5, "warning at line 5, column 5: mod.kick in role mod is already granted by the wildcard mod.*".to_string()),
+                (7, 5, "warning at line 7, column 5: role mod lists mod.* more than once".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_about_nodes_repeated_from_parents() {
+        let content = "\
+[roles.helper]
+level = 1
+permissions = [\"chat.mute\"]
+
+[roles.mod]
+level = 5
+permissions = [\"chat.mute\"]
+inherits = [\"helper\", \"ghost\"]
+";
+        assert_eq!(
+            diagnostics(content),
+            [
+                (7, 16, "warning at line 7, column 16: chat.mute in role mod is already inherited from role helper".to_string()),
+                (8, 12, "warning at line 8, column 12: role mod inherits unknown role ghost".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_inheritance_cycles() {
+        let content = "\
+[roles.a]
+level = 1
+permissions = []
+inherits = [\"b\"]
+
+[roles.b]
+level = 2
+permissions = []
+inherits = [\"a\"]
+";
+        let report = check(content);
+        assert!(report.has_errors());
+        let cycle = report
+            .diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.message.starts_with("role inheritance cycle: "))
+            .expect("cycle reported");
+        assert_eq!(cycle.severity, Severity::Error);
+        assert_eq!(cycle.location, Some((4, 12)));
+        assert_eq!(cycle.message, "role inheritance cycle: a -> b -> a");
+    }
+
+    #[test]
+    fn unreadable_files_have_no_value() {
+        let report = check("[roles.admin\nlevel = 1\n");
+        assert!(report.value.is_none());
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(report.diagnostics[0].location.map(|(line, _)| line), Some(1));
+    }
+}
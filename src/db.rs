@@ -1,34 +1,121 @@
-use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool};
+use sqlx::{migrate::{MigrateDatabase, Migrator}, sqlite::SqliteConnectOptions, Row, Sqlite, SqlitePool};
 use std::sync::Arc;
 use tokio::sync::OnceCell;
 use std::path::Path;
+use std::str::FromStr;
+
+use crate::utils::unix_now;
 
 static DB_INSTANCE: OnceCell<Arc<DB>> = OnceCell::const_new();
 
+// Embedded from ./migrations at build time, applied in version order
+static MIGRATOR: Migrator = sqlx::migrate!();
+
 pub struct DB {
     pub pool: SqlitePool,
+    pub path: String,
 }
 
 impl DB {
     #[allow(dead_code)]
     pub async fn init(path: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let db_url = format!("sqlite:{}", path);
-        
+
         if !Path::new(path).exists() {
             log::info!("Creating database at {}", path);
             Sqlite::create_database(&db_url).await?;
         }
 
-        let pool = SqlitePool::connect(&db_url).await?;
+        // Enforced per connection, so set on the pool rather than once
+        let options = SqliteConnectOptions::from_str(&db_url)?.foreign_keys(true);
+        let pool = SqlitePool::connect_with(options).await?;
         log::info!("Database connection established");
 
-        Ok(DB { pool })
+        Ok(DB { pool, path: path.to_string() })
+    }
+
+    /// Applies every pending migration, backing the database up first when it
+    /// already holds data.
+    pub async fn migrate(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let tables: Vec<String> = sqlx::query("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'")
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| row.get("name"))
+            .collect();
+
+        let applied: Vec<i64> = if tables.iter().any(|name| name == "_sqlx_migrations") {
+            sqlx::query("SELECT version FROM _sqlx_migrations WHERE success = 1")
+                .fetch_all(&self.pool)
+                .await?
+                .iter()
+                .map(|row| row.get("version"))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let current = applied.iter().copied().max().unwrap_or(0);
+
+        let pending: Vec<i64> = MIGRATOR
+            .iter()
+            .map(|migration| migration.version)
+            .filter(|version| !applied.contains(version))
+            .collect();
+        if pending.is_empty() {
+            log::info!("[HysterionPerms] Database schema is up to date (version {})", current);
+            return Ok(());
+        }
+
+        if !tables.is_empty() {
+            let backup = format!("{}.v{}-{}.bak", self.path, current, unix_now());
+            log::info!("[HysterionPerms] Backing up database to {} before migrating", backup);
+            sqlx::query("VACUUM INTO $1")
+                .bind(&backup)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        // Databases from before versioned migrations have no migration record,
+        // and their tables may predate some baseline columns
+        if applied.is_empty() && tables.iter().any(|name| name == "player_roles") {
+            self.ensure_column("player_roles", "expires_at", "INTEGER").await?;
+            self.ensure_column("player_permissions", "expires_at", "INTEGER").await?;
+            self.ensure_column("player_roles", "contexts", "TEXT NOT NULL DEFAULT ''").await?;
+            self.ensure_column("player_permissions", "contexts", "TEXT NOT NULL DEFAULT ''").await?;
+        }
+
+        MIGRATOR.run(&self.pool).await?;
+
+        let version = pending.iter().copied().max().unwrap_or(current).max(current);
+        log::info!(
+            "[HysterionPerms] Migrated database schema from version {} to {} ({} migration(s))",
+            current, version, pending.len()
+        );
+        Ok(())
+    }
+
+    async fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<(), sqlx::Error> {
+        let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(&self.pool)
+            .await?;
+        let exists = columns.iter().any(|row| row.get::<String, _>("name") == column);
+
+        // An empty result means the table itself is missing; the baseline creates it
+        if !columns.is_empty() && !exists {
+            log::info!("[HysterionPerms] Adding column {}.{}", table, column);
+            sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
     }
 }
 
 #[allow(dead_code)]
 pub async fn setup_db(path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let db = DB::init(path).await?;
+    db.migrate().await?;
     if let Err(e) = DB_INSTANCE.set(Arc::new(db)) {
         return Err(format!("Failed to set DB instance: {}", e).into());
     }
@@ -38,4 +125,4 @@ pub async fn setup_db(path: &str) -> Result<(), Box<dyn std::error::Error + Send
 #[allow(dead_code)]
 pub async fn get_db() -> Arc<DB> {
     DB_INSTANCE.get().expect("Database not initialized").clone()
-} 
\ No newline at end of file
+}
@@ -0,0 +1,42 @@
+use async_trait::async_trait;
+use pumpkin::{
+    plugin::{
+        player::{player_join::PlayerJoinEvent, player_leave::PlayerLeaveEvent, PlayerEvent},
+        EventHandler,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+use std::sync::Arc;
+
+use crate::{permissions::{cache, context, expiry}, utils::neutral_colour};
+
+/// Resolves a joining player's permissions up front so their first check
+/// is a cache hit, starts tracking them for context lookups, and delivers
+/// role expiry notices missed while offline.
+pub struct PlayerJoinHandler;
+
+#[async_trait]
+impl EventHandler<PlayerJoinEvent> for PlayerJoinHandler {
+    async fn handle(&self, _server: &Arc<Server>, event: &PlayerJoinEvent) {
+        let player = event.get_player();
+        context::track_player(player);
+        cache::resolve(&player.gameprofile.id);
+
+        for notice in expiry::take_notices(&player.gameprofile.id) {
+            player
+                .send_system_message(&TextComponent::text(notice).color_rgb(neutral_colour()))
+                .await;
+        }
+    }
+}
+
+/// Stops tracking a leaving player's contexts.
+pub struct PlayerLeaveHandler;
+
+#[async_trait]
+impl EventHandler<PlayerLeaveEvent> for PlayerLeaveHandler {
+    async fn handle(&self, _server: &Arc<Server>, event: &PlayerLeaveEvent) {
+        context::untrack_player(&event.get_player().gameprofile.id);
+    }
+}
@@ -3,15 +3,17 @@ mod permissions;
 mod utils;
 mod db;
 mod config;
+mod events;
 
 use std::path::PathBuf;
 use pumpkin::plugin::api::context::Context;
+use pumpkin::plugin::EventPriority;
 use pumpkin_util::permission::PermissionLvl;
 use pumpkin_api_macros::{plugin_impl, plugin_method};
 use crate::commands::perms::PermsCommand;
 use crate::commands::Command;
 use tokio::runtime::Runtime;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 use env_logger;
 
 static RUNTIME: OnceLock<Runtime> = OnceLock::new();
@@ -49,42 +51,37 @@ pub async fn on_load(&mut self, server: &Context) -> Result<(), String> {
         return Err(format!("Failed to initialize config: {}", e));
     }
     
-    // Initialize database
+    // Initialize database and apply pending schema migrations
     let db_path = data_dir.join("hysterion_perms.db");
     if let Err(e) = db::setup_db(db_path.to_str().unwrap()).await {
         log::error!("Failed to initialize database: {}", e);
         return Err(format!("Failed to initialize database: {}", e));
     }
     
-    // Initialize permissions tables
-    if let Err(e) = permissions::init_tables().await {
-        log::error!("Failed to initialize permission tables: {}", e);
-        return Err(format!("Failed to initialize permission tables: {}", e));
-    }
-    
     // Get config and initialize roles
     let config = config::get_config().await;
-    
-    // Initialize roles from config
-    for (role_name, role_config) in &config.value.roles {
-        if let Err(e) = permissions::create_role(role_name, role_config.level).await {
-            log::error!("Failed to create role {}: {}", role_name, e);
-            continue;
-        }
-        
-        // Add permissions to role
-        for permission in &role_config.permissions {
-            if let Err(e) = permissions::add_role_permission(role_name, permission).await {
-                log::warn!("Failed to add permission {} to role {}: {}", permission, role_name, e);
-            }
-        }
+
+    // Apply the configured roles according to the reconcile mode
+    if let Err(e) = permissions::reconcile::apply(&config.value).await {
+        log::error!("Failed to apply config: {}", e);
+        return Err(format!("Failed to apply config: {}", e));
     }
     
     // Initialize permission system with server context
     permissions::init_permission_system(server).await;
+    permissions::expiry::spawn_cleanup_task(server.server.clone());
+    config::spawn_watcher();
+
+    server
+        .register_event(Arc::new(events::PlayerJoinHandler), EventPriority::Lowest, false)
+        .await;
+    server
+        .register_event(Arc::new(events::PlayerLeaveHandler), EventPriority::Lowest, false)
+        .await;
 
+    // Open to everyone; the tree checks "hysterion.perms", then each subcommand its own node
     server
-        .register_command(PermsCommand::init_command(), PermissionLvl::Four)
+        .register_command(PermsCommand::init_command(), PermissionLvl::Zero)
         .await;
 
     log::info!("[Hysterion (perms)] Commands registered successfully!");
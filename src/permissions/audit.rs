@@ -0,0 +1,265 @@
+// Append-only record of every permission change, and of changes that were refused.
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection};
+
+use super::authority::Actor;
+use crate::{db::get_db, utils::unix_now};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    RoleCreate,
+    RoleDelete,
+    RoleRename,
+    RoleClone,
+    RoleParents,
+    RolePermissionAdd,
+    RolePermissionRemove,
+    PlayerRoleAdd,
+    PlayerRoleRemove,
+    PlayerRoleMove,
+    PlayerRoleExpire,
+    PlayerPermissionAdd,
+    PlayerPermissionRemove,
+    PlayerPermissionExpire,
+    Denied,
+}
+
+impl AuditAction {
+    const ALL: [AuditAction; 15] = [
+        AuditAction::RoleCreate,
+        AuditAction::RoleDelete,
+        AuditAction::RoleRename,
+        AuditAction::RoleClone,
+        AuditAction::RoleParents,
+        AuditAction::RolePermissionAdd,
+        AuditAction::RolePermissionRemove,
+        AuditAction::PlayerRoleAdd,
+        AuditAction::PlayerRoleRemove,
+        AuditAction::PlayerRoleMove,
+        AuditAction::PlayerRoleExpire,
+        AuditAction::PlayerPermissionAdd,
+        AuditAction::PlayerPermissionRemove,
+        AuditAction::PlayerPermissionExpire,
+        AuditAction::Denied,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::RoleCreate => "role.create",
+            AuditAction::RoleDelete => "role.delete",
+            AuditAction::RoleRename => "role.rename",
+            AuditAction::RoleClone => "role.clone",
+            AuditAction::RoleParents => "role.parents",
+            AuditAction::RolePermissionAdd => "role.permission.add",
+            AuditAction::RolePermissionRemove => "role.permission.remove",
+            AuditAction::PlayerRoleAdd => "player.role.add",
+            AuditAction::PlayerRoleRemove => "player.role.remove",
+            AuditAction::PlayerRoleMove => "player.role.move",
+            AuditAction::PlayerRoleExpire => "player.role.expire",
+            AuditAction::PlayerPermissionAdd => "player.permission.add",
+            AuditAction::PlayerPermissionRemove => "player.permission.remove",
+            AuditAction::PlayerPermissionExpire => "player.permission.expire",
+            AuditAction::Denied => "denied",
+        }
+    }
+}
+
+/// A change about to be recorded. `target` is the player UUID or role name
+/// that was changed and `subject` the node or role it concerns. Old and new
+/// values hold whatever is needed to reverse the change, usually as JSON.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub action: AuditAction,
+    pub target: String,
+    pub subject: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl Change {
+    pub fn new(action: AuditAction, target: impl Into<String>) -> Self {
+        Self {
+            action,
+            target: target.into(),
+            subject: None,
+            old_value: None,
+            new_value: None,
+            reason: None,
+        }
+    }
+
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    pub fn old_value(mut self, value: impl Into<String>) -> Self {
+        self.old_value = Some(value.into());
+        self
+    }
+
+    pub fn new_value(mut self, value: impl Into<String>) -> Self {
+        self.new_value = Some(value.into());
+        self
+    }
+
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+/// A stored audit entry.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor: String,
+    pub action: String,
+    pub target: String,
+    pub subject: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub reason: Option<String>,
+    pub created_at: i64,
+}
+
+/// Writes one entry on `conn`, normally the transaction making the change so
+/// the entry and the change are committed together.
+pub async fn record(conn: &mut SqliteConnection, actor: &Actor, change: Change) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO audit_log (actor, action, target, subject, old_value, new_value, reason, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+    )
+    .bind(actor.to_string())
+    .bind(change.action.as_str())
+    .bind(change.target)
+    .bind(change.subject)
+    .bind(change.old_value)
+    .bind(change.new_value)
+    .bind(change.reason)
+    .bind(unix_now())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Records a change that was refused, on its own connection.
+pub async fn record_denied(actor: &Actor, target: &str, attempted: &str, reason: &str) -> Result<(), sqlx::Error> {
+    let db = get_db().await;
+    let mut conn = db.pool.acquire().await?;
+    record(&mut conn, actor, Change::new(AuditAction::Denied, target).subject(attempted).reason(reason)).await
+}
+
+fn entry_from_row(row: &sqlx::sqlite::SqliteRow) -> AuditEntry {
+    AuditEntry {
+        id: row.get("id"),
+        actor: row.get("actor"),
+        action: row.get("action"),
+        target: row.get("target"),
+        subject: row.get("subject"),
+        old_value: row.get("old_value"),
+        new_value: row.get("new_value"),
+        reason: row.get("reason"),
+        created_at: row.get("created_at"),
+    }
+}
+
+pub async fn get_entry(id: i64) -> Result<Option<AuditEntry>, sqlx::Error> {
+    let db = get_db().await;
+
+    let row = sqlx::query("SELECT * FROM audit_log WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&db.pool)
+        .await?;
+    Ok(row.as_ref().map(entry_from_row))
+}
+
+/// Changes made by `actor` at or after `since`, newest first. Refused
+/// changes and changes that were already undone are left out.
+pub async fn changes_since(actor: &str, since: i64) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    let db = get_db().await;
+
+    let entries = sqlx::query(
+        "SELECT * FROM audit_log
+         WHERE actor = $1 AND created_at >= $2 AND action != $3
+           AND NOT EXISTS (SELECT 1 FROM audit_log AS undo WHERE undo.reason = 'undo #' || audit_log.id)
+         ORDER BY id DESC"
+    )
+    .bind(actor)
+    .bind(since)
+    .bind(AuditAction::Denied.as_str())
+    .fetch_all(&db.pool)
+    .await?
+    .iter()
+    .map(entry_from_row)
+    .collect();
+    Ok(entries)
+}
+
+/// Whether entry `id` has already been undone.
+pub async fn is_undone(conn: &mut SqliteConnection, id: i64) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query("SELECT COUNT(*) AS count FROM audit_log WHERE reason = $1")
+        .bind(format!("undo #{}", id))
+        .fetch_one(conn)
+        .await?;
+    Ok(row.get::<i64, _>("count") > 0)
+}
+
+/// Which entries [`query`] returns. Every field left empty matches anything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub target: Option<String>,
+    pub actor: Option<String>,
+    /// Only entries at or after this time (unix seconds).
+    pub since: Option<i64>,
+    /// Only entries at or before this time (unix seconds).
+    pub until: Option<i64>,
+}
+
+/// Returns one page of matching entries, newest first, and the total count.
+pub async fn query(filter: &AuditFilter, page: u32, page_size: u32) -> Result<(Vec<AuditEntry>, i64), sqlx::Error> {
+    let db = get_db().await;
+
+    let total: i64 = filtered(QueryBuilder::new("SELECT COUNT(*) AS count FROM audit_log WHERE 1 = 1"), filter)
+        .build()
+        .fetch_one(&db.pool)
+        .await?
+        .get("count");
+
+    let mut builder = filtered(QueryBuilder::new("SELECT * FROM audit_log WHERE 1 = 1"), filter);
+    builder
+        .push(" ORDER BY id DESC LIMIT ")
+        .push_bind(i64::from(page_size))
+        .push(" OFFSET ")
+        .push_bind(i64::from(page.saturating_sub(1)) * i64::from(page_size));
+
+    let entries = builder
+        .build()
+        .fetch_all(&db.pool)
+        .await?
+        .iter()
+        .map(entry_from_row)
+        .collect();
+
+    Ok((entries, total))
+}
+
+fn filtered<'a>(mut builder: QueryBuilder<'a, Sqlite>, filter: &AuditFilter) -> QueryBuilder<'a, Sqlite> {
+    if let Some(target) = &filter.target {
+        builder.push(" AND target = ").push_bind(target.clone());
+    }
+    if let Some(actor) = &filter.actor {
+        builder.push(" AND actor = ").push_bind(actor.clone());
+    }
+    if let Some(since) = filter.since {
+        builder.push(" AND created_at >= ").push_bind(since);
+    }
+    if let Some(until) = filter.until {
+        builder.push(" AND created_at <= ").push_bind(until);
+    }
+    builder
+}
@@ -0,0 +1,149 @@
+// Delegation rules that stop senders from handing out more power than they have.
+//
+// A player sender's authority is the highest level among the roles they hold
+// or inherit. They may only assign or edit roles below that level, only
+// change players whose own highest level is below it, and only grant nodes
+// they hold themselves. The console and RCON are trusted and bypass all of it.
+use std::fmt;
+use pumpkin::command::CommandSender;
+use uuid::Uuid;
+
+use super::context::ContextSet;
+use super::node::PermissionNode;
+use super::{cache, inheritance, snapshot};
+use crate::utils::unix_now;
+
+/// Who is making a change. `System` covers changes the plugin makes itself,
+/// such as loading the config or removing expired grants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Actor {
+    Console,
+    Player(Uuid),
+    System,
+}
+
+impl Actor {
+    pub fn from_sender(sender: &CommandSender<'_>) -> Self {
+        match sender {
+            CommandSender::Player(player) => Actor::Player(player.gameprofile.id),
+            _ => Actor::Console,
+        }
+    }
+
+    /// The actor's highest role level, or `None` when it is not limited.
+    pub fn level(&self) -> Option<i32> {
+        match self {
+            Actor::Console | Actor::System => None,
+            Actor::Player(uuid) => Some(highest_level(uuid)),
+        }
+    }
+
+    /// Whether the actor may assign, remove or edit `role_name`.
+    pub fn check_role(&self, role_name: &str) -> Result<(), Denied> {
+        let Some(level) = self.level() else {
+            return Ok(());
+        };
+        let role_level = role_level(role_name);
+        if role_level < level {
+            Ok(())
+        } else {
+            Err(Denied(format!(
+                "Role {} has level {}, but you can only manage roles below your level {}",
+                role_name, role_level, level
+            )))
+        }
+    }
+
+    /// Whether the actor may give a role of `role_level`, e.g. one being created.
+    pub fn check_level(&self, role_level: i32) -> Result<(), Denied> {
+        match self.level() {
+            Some(level) if role_level >= level => Err(Denied(format!(
+                "You can only manage roles below your level {}",
+                level
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the actor may change the permissions or roles of `target`.
+    pub fn check_player(&self, target: &Uuid, target_name: &str) -> Result<(), Denied> {
+        let Some(level) = self.level() else {
+            return Ok(());
+        };
+        let target_level = highest_level(target);
+        if target_level < level {
+            Ok(())
+        } else {
+            Err(Denied(format!(
+                "{} has level {}, but you can only change players below your level {}",
+                target_name, target_level, level
+            )))
+        }
+    }
+
+    /// Whether the actor may grant `node` within `contexts`. Denying a node
+    /// (`-node`) counts as granting it, so it also requires holding the node.
+    pub fn check_node(&self, node: &str, contexts: &ContextSet) -> Result<(), Denied> {
+        let Actor::Player(uuid) = self else {
+            return Ok(());
+        };
+        let node = PermissionNode::parse(node);
+        let effective = cache::resolve(uuid);
+        // A wildcard needs every node it matches, not just its own spelling
+        let held = if node.is_wildcard() {
+            effective.covers(node.pattern, contexts)
+        } else {
+            effective.has_permission(node.pattern, contexts)
+        };
+        if held {
+            Ok(())
+        } else {
+            Err(Denied(format!("You can only grant nodes you hold yourself, and you do not hold {}", node.pattern)))
+        }
+    }
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Actor::Console => write!(f, "console"),
+            Actor::Player(uuid) => write!(f, "{}", uuid),
+            Actor::System => write!(f, "system"),
+        }
+    }
+}
+
+/// A change refused by the delegation rules, with the reason shown to the sender.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Denied(pub String);
+
+impl fmt::Display for Denied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Level of a role, 0 for roles the snapshot does not know yet.
+pub fn role_level(role_name: &str) -> i32 {
+    snapshot::current().roles.get(role_name).map_or(0, |role| role.level)
+}
+
+/// Highest level among the unexpired roles a player holds or inherits, 0 for none.
+pub fn highest_level(uuid: &Uuid) -> i32 {
+    let snapshot = snapshot::current();
+    let Some(player) = snapshot.players.get(uuid) else {
+        return 0;
+    };
+
+    let now = unix_now();
+    let held: Vec<String> = player.roles
+        .iter()
+        .filter(|membership| !membership.is_expired(now))
+        .map(|membership| membership.role.clone())
+        .collect();
+    inheritance::resolve_roles(&held, |name| snapshot.roles.get(name))
+        .into_iter()
+        .map(|(role, _)| role.level)
+        .max()
+        .unwrap_or(0)
+}
@@ -0,0 +1,158 @@
+// Per-player cache of resolved effective permissions.
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, RwLock};
+use uuid::Uuid;
+
+use super::context::ContextSet;
+use super::node::{self, GrantSource, PermissionNode};
+use super::snapshot;
+use crate::utils::unix_now;
+
+/// A stored node together with where it came from, when it stops applying
+/// and which contexts it is limited to.
+#[derive(Debug, Clone)]
+pub struct Grant {
+    pub node: String,
+    pub source: GrantSource,
+    pub expires_at: Option<i64>,
+    pub contexts: ContextSet,
+}
+
+/// A player's direct and role nodes flattened into one list, plus the roles
+/// that list was built from so role edits can find the entries they affect.
+#[derive(Debug, Clone, Default)]
+pub struct EffectivePermissions {
+    pub grants: Vec<Grant>,
+    pub depends_on: HashSet<String>,
+}
+
+impl EffectivePermissions {
+    /// Expired grants are skipped here, so they stop applying even before the
+    /// cleanup task deletes them, as are grants scoped to other contexts.
+    pub fn has_permission(&self, permission: &str, contexts: &ContextSet) -> bool {
+        let now = unix_now();
+        let grants = self
+            .grants
+            .iter()
+            .filter(|grant| grant.expires_at.is_none_or(|expires_at| expires_at > now))
+            .filter(|grant| grant.contexts.is_satisfied_by(contexts))
+            .map(|grant| (grant.node.as_str(), grant.source));
+        match node::resolve(grants, permission) {
+            Some(allowed) => allowed,
+            None => {
+                log::debug!("[HysterionPerms] No matching permissions found for '{}'", permission);
+                false
+            }
+        }
+    }
+
+    /// Whether every node `pattern` can match is allowed, for granting
+    /// wildcards: an allowing grant must contain the whole pattern, and no
+    /// denial may overlap it.
+    pub fn covers(&self, pattern: &str, contexts: &ContextSet) -> bool {
+        let now = unix_now();
+        let active: Vec<PermissionNode> = self
+            .grants
+            .iter()
+            .filter(|grant| grant.expires_at.is_none_or(|expires_at| expires_at > now))
+            .filter(|grant| grant.contexts.is_satisfied_by(contexts))
+            .map(|grant| PermissionNode::parse(&grant.node))
+            .collect();
+
+        let allowed = active.iter().any(|held| !held.negated && node::pattern_covers(held.pattern, pattern));
+        let denied = active.iter().any(|held| held.negated && node::patterns_overlap(held.pattern, pattern));
+        allowed && !denied
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Default)]
+struct PermissionCache {
+    entries: RwLock<HashMap<Uuid, Arc<EffectivePermissions>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+static CACHE: LazyLock<PermissionCache> = LazyLock::new(PermissionCache::default);
+
+pub fn get(uuid: &Uuid) -> Option<Arc<EffectivePermissions>> {
+    let entry = CACHE.entries.read().unwrap().get(uuid).cloned();
+    let counter = if entry.is_some() { &CACHE.hits } else { &CACHE.misses };
+    counter.fetch_add(1, Ordering::Relaxed);
+    entry
+}
+
+/// Returns the cached entry, resolving it from the current snapshot on a miss.
+pub fn resolve(uuid: &Uuid) -> Arc<EffectivePermissions> {
+    if let Some(effective) = get(uuid) {
+        return effective;
+    }
+
+    let snapshot = snapshot::current();
+    let effective = Arc::new(snapshot.effective_permissions_of(uuid));
+
+    // A snapshot swapped in meanwhile invalidates after the swap, so only
+    // entries built from the live snapshot may be stored
+    let mut entries = CACHE.entries.write().unwrap();
+    if snapshot::current().version == snapshot.version {
+        entries.insert(*uuid, effective.clone());
+    }
+    effective
+}
+
+pub fn invalidate_player(uuid: &Uuid) {
+    CACHE.entries.write().unwrap().remove(uuid);
+}
+
+/// Drops every entry that was resolved through `role_name`, directly or by inheritance.
+pub fn invalidate_role(role_name: &str) {
+    CACHE.entries
+        .write()
+        .unwrap()
+        .retain(|_, effective| !effective.depends_on.contains(role_name));
+}
+
+pub fn stats() -> CacheStats {
+    CacheStats {
+        entries: CACHE.entries.read().unwrap().len(),
+        hits: CACHE.hits.load(Ordering::Relaxed),
+        misses: CACHE.misses.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effective(nodes: &[&str]) -> EffectivePermissions {
+        let grants = nodes
+            .iter()
+            .map(|node| Grant {
+                node: node.to_string(),
+                source: GrantSource::Direct,
+                expires_at: None,
+                contexts: ContextSet::new(),
+            })
+            .collect();
+        EffectivePermissions { grants, depends_on: HashSet::new() }
+    }
+
+    #[test]
+    fn covers_rejects_patterns_a_denial_overlaps() {
+        let held = effective(&["a.**", "-a.*.c"]);
+        let contexts = ContextSet::new();
+        assert!(held.covers("a.b.d", &contexts));
+        assert!(held.covers("a.b", &contexts));
+        assert!(!held.covers("a.b.*", &contexts));
+        assert!(!held.covers("a.b.c", &contexts));
+        assert!(!held.covers("a.**", &contexts));
+        assert!(!held.covers("b.*", &contexts));
+    }
+}
@@ -0,0 +1,167 @@
+// Contexts limit where a grant applies, e.g. `world=creative`.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, LazyLock, RwLock, Weak};
+use pumpkin::entity::player::Player;
+use pumpkin_registry::VanillaDimensionType;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A set of `key=value` pairs. As a grant requirement every pair must match
+/// the player's current contexts; an empty set applies everywhere.
+///
+/// Keys and values are lowercased on the way in, including when read from
+/// config.toml or the audit log.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "BTreeMap<String, String>", into = "BTreeMap<String, String>")]
+pub struct ContextSet(BTreeMap<String, String>);
+
+impl ContextSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.0.insert(key.into().to_lowercase(), value.into().to_lowercase());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Whether every requirement in `self` holds in `current`.
+    pub fn is_satisfied_by(&self, current: &ContextSet) -> bool {
+        self.iter().all(|(key, value)| current.get(key) == Some(value))
+    }
+
+    /// Combines two requirements, or `None` if they can never hold together
+    /// (the same key with different values).
+    pub fn merged(&self, other: &ContextSet) -> Option<ContextSet> {
+        let mut merged = self.clone();
+        for (key, value) in other.iter() {
+            match merged.get(key) {
+                Some(existing) if existing != value => return None,
+                _ => merged.insert(key, value),
+            }
+        }
+        Some(merged)
+    }
+
+    /// Parses the stored form, `key=value` pairs separated by commas.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut contexts = ContextSet::new();
+        for pair in input.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            match pair.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+                    contexts.insert(key.trim(), value.trim());
+                },
+                _ => return Err(format!("Invalid context '{}', expected key=value", pair)),
+            }
+        }
+        Ok(contexts)
+    }
+}
+
+impl From<BTreeMap<String, String>> for ContextSet {
+    fn from(pairs: BTreeMap<String, String>) -> Self {
+        let mut contexts = ContextSet::new();
+        for (key, value) in pairs {
+            contexts.insert(key, value);
+        }
+        contexts
+    }
+}
+
+impl From<ContextSet> for BTreeMap<String, String> {
+    fn from(contexts: ContextSet) -> Self {
+        contexts.0
+    }
+}
+
+impl fmt::Display for ContextSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs: Vec<String> = self.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+        write!(f, "{}", pairs.join(","))
+    }
+}
+
+// Online players, so checks can read their state without awaiting the server
+static ONLINE_PLAYERS: LazyLock<RwLock<HashMap<Uuid, Weak<Player>>>> = LazyLock::new(Default::default);
+
+pub fn track_player(player: &Arc<Player>) {
+    ONLINE_PLAYERS
+        .write()
+        .unwrap()
+        .insert(player.gameprofile.id, Arc::downgrade(player));
+}
+
+pub fn untrack_player(uuid: &Uuid) {
+    ONLINE_PLAYERS.write().unwrap().remove(uuid);
+}
+
+/// Adds contexts for a player whenever one of their permissions is checked.
+/// Calculators run synchronously inside permission checks, so they must
+/// answer from memory and never block.
+pub trait ContextCalculator: Send + Sync {
+    fn calculate(&self, player: &Player, contexts: &mut ContextSet);
+}
+
+/// Built-in calculator for `world`, `dimension` and `gamemode`.
+struct PlayerStateCalculator;
+
+impl ContextCalculator for PlayerStateCalculator {
+    fn calculate(&self, player: &Player, contexts: &mut ContextSet) {
+        let world = player.world();
+        if let Some(name) = world.level.level_folder.root_folder.file_name() {
+            contexts.insert("world", name.to_string_lossy());
+        }
+        contexts.insert("dimension", dimension_name(world.dimension_type));
+        contexts.insert("gamemode", snake_case(&format!("{:?}", player.gamemode.load())));
+    }
+}
+
+// Run in order; when two calculators set the same key, the later one wins
+static CALCULATORS: LazyLock<Vec<Box<dyn ContextCalculator>>> = LazyLock::new(|| vec![Box::new(PlayerStateCalculator)]);
+
+/// Collects the player's contexts from every calculator.
+/// Offline players have no contexts, so only unscoped grants apply to them.
+pub fn current_contexts(uuid: &Uuid) -> ContextSet {
+    let mut contexts = ContextSet::new();
+    let player = ONLINE_PLAYERS.read().unwrap().get(uuid).and_then(Weak::upgrade);
+
+    if let Some(player) = player {
+        for calculator in CALCULATORS.iter() {
+            calculator.calculate(&player, &mut contexts);
+        }
+    }
+    contexts
+}
+
+fn dimension_name(dimension: VanillaDimensionType) -> &'static str {
+    match dimension {
+        VanillaDimensionType::Overworld => "overworld",
+        VanillaDimensionType::OverworldCaves => "overworld_caves",
+        VanillaDimensionType::TheEnd => "the_end",
+        VanillaDimensionType::TheNether => "the_nether",
+    }
+}
+
+// `Creative` -> `creative`
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
@@ -0,0 +1,110 @@
+// Background removal of expired grants and role memberships.
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use pumpkin::server::Server;
+use pumpkin_util::text::TextComponent;
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::audit::{self, AuditAction, Change};
+use super::authority::Actor;
+use super::{direct_permission_from_row, membership_from_row, snapshot};
+use crate::{db::get_db, get_runtime, utils::{neutral_colour, unix_now}};
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(30);
+
+// Notices for players who were offline when their role expired, shown on join
+static PENDING_NOTICES: LazyLock<Mutex<HashMap<Uuid, Vec<String>>>> = LazyLock::new(Default::default);
+
+/// Deletes every expired direct permission and role membership, returning
+/// the expired memberships so their players can be told.
+pub async fn remove_expired() -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+    let db = get_db().await;
+    let now = unix_now();
+    let mut tx = db.pool.begin().await?;
+
+    let permissions = sqlx::query(
+        "DELETE FROM player_permissions WHERE expires_at IS NOT NULL AND expires_at <= $1
+         RETURNING player_uuid, permission, expires_at, contexts"
+    )
+    .bind(now)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let memberships = sqlx::query(
+        "DELETE FROM player_roles WHERE expires_at IS NOT NULL AND expires_at <= $1
+         RETURNING player_uuid, role_name, expires_at, contexts"
+    )
+    .bind(now)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for row in &permissions {
+        let grant = direct_permission_from_row(row);
+        let change = Change::new(AuditAction::PlayerPermissionExpire, row.get::<String, _>("player_uuid"))
+            .subject(grant.permission.clone())
+            .old_value(serde_json::to_string(&vec![&grant]).unwrap());
+        audit::record(&mut tx, &Actor::System, change).await?;
+    }
+    for row in &memberships {
+        let membership = membership_from_row(row);
+        let change = Change::new(AuditAction::PlayerRoleExpire, row.get::<String, _>("player_uuid"))
+            .subject(membership.role.clone())
+            .old_value(serde_json::to_string(&vec![&membership]).unwrap());
+        audit::record(&mut tx, &Actor::System, change).await?;
+    }
+
+    tx.commit().await?;
+
+    let memberships: Vec<(Uuid, String)> = memberships
+        .into_iter()
+        .filter_map(|row| {
+            let uuid = Uuid::parse_str(row.get("player_uuid")).ok()?;
+            Some((uuid, row.get("role_name")))
+        })
+        .collect();
+
+    if !permissions.is_empty() || !memberships.is_empty() {
+        log::info!(
+            "[HysterionPerms] Removed {} expired permission(s) and {} expired role membership(s)",
+            permissions.len(),
+            memberships.len()
+        );
+        snapshot::schedule_refresh();
+    }
+    Ok(memberships)
+}
+
+async fn notify_expired(server: &Server, memberships: Vec<(Uuid, String)>) {
+    for (uuid, role_name) in memberships {
+        let notice = format!("Your role {} has expired", role_name);
+        match server.get_player_by_uuid(uuid).await {
+            Some(player) => {
+                player
+                    .send_system_message(&TextComponent::text(notice).color_rgb(neutral_colour()))
+                    .await;
+            },
+            None => PENDING_NOTICES.lock().unwrap().entry(uuid).or_default().push(notice),
+        }
+    }
+}
+
+/// Takes the expiry notices queued while the player was offline.
+pub fn take_notices(uuid: &Uuid) -> Vec<String> {
+    PENDING_NOTICES.lock().unwrap().remove(uuid).unwrap_or_default()
+}
+
+/// Runs [`remove_expired`] on the plugin runtime every [`CLEANUP_INTERVAL`].
+pub fn spawn_cleanup_task(server: Arc<Server>) {
+    get_runtime().spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match remove_expired().await {
+                Ok(memberships) => notify_expired(&server, memberships).await,
+                Err(e) => log::error!("[HysterionPerms] Failed to remove expired grants: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,71 @@
+// Role inheritance graph helpers.
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::Role;
+
+/// Looks for a cycle in a role -> parents graph.
+///
+/// Returns the offending path, starting and ending with the same role, so it
+/// can be reported as `admin -> moderator -> admin`.
+pub fn find_cycle<'a>(graph: &HashMap<&'a str, Vec<&'a str>>) -> Option<Vec<&'a str>> {
+    fn visit<'a>(
+        role: &'a str,
+        graph: &HashMap<&'a str, Vec<&'a str>>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Option<Vec<&'a str>> {
+        if let Some(start) = path.iter().position(|r| *r == role) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(role);
+            return Some(cycle);
+        }
+        if done.contains(role) {
+            return None;
+        }
+
+        path.push(role);
+        for parent in graph.get(role).into_iter().flatten() {
+            if let Some(cycle) = visit(parent, graph, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        done.insert(role);
+        None
+    }
+
+    let mut roles: Vec<_> = graph.keys().copied().collect();
+    roles.sort_unstable();
+
+    let mut done = HashSet::new();
+    roles
+        .into_iter()
+        .find_map(|role| visit(role, graph, &mut Vec::new(), &mut done))
+}
+
+/// Looks up `roles` and everything they inherit, breadth first.
+///
+/// Each role is paired with its distance from the player (0 for roles held
+/// directly) and is only visited once, so a cycle stored in the database
+/// cannot make the walk loop.
+pub fn resolve_roles<'a>(roles: &[String], lookup: impl Fn(&str) -> Option<&'a Role>) -> Vec<(&'a Role, usize)> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut queue: VecDeque<(&str, usize)> = roles.iter().map(|r| (r.as_str(), 0)).collect();
+    let mut resolved = Vec::new();
+
+    while let Some((role_name, depth)) = queue.pop_front() {
+        if !seen.insert(role_name) {
+            continue;
+        }
+
+        match lookup(role_name) {
+            Some(role) => {
+                queue.extend(role.parents.iter().map(|parent| (parent.as_str(), depth + 1)));
+                resolved.push((role, depth));
+            },
+            None => log::error!("[HysterionPerms] Unknown role {}", role_name),
+        }
+    }
+
+    resolved
+}
@@ -0,0 +1,60 @@
+// Nodes the plugin knows about, for suggesting them in commands: those in
+// config.toml, those granted to roles or players, and those asked for by
+// permission checks since startup.
+use std::collections::BTreeSet;
+use std::sync::{LazyLock, RwLock};
+
+use super::snapshot;
+use crate::config;
+
+static CHECKED: LazyLock<RwLock<BTreeSet<String>>> = LazyLock::new(Default::default);
+
+/// Remembers a node a permission check asked for.
+pub fn record_checked(node: &str) {
+    if CHECKED.read().unwrap().contains(node) {
+        return;
+    }
+    CHECKED.write().unwrap().insert(node.to_string());
+}
+
+/// Every known node, without its negation.
+pub fn nodes() -> BTreeSet<String> {
+    let mut nodes = CHECKED.read().unwrap().clone();
+    let mut add = |node: &str| {
+        nodes.insert(node.strip_prefix('-').unwrap_or(node).to_string());
+    };
+
+    if let Some(config) = config::current() {
+        for role in config.value.roles.values() {
+            let scoped = role.scoped.iter().flat_map(|scoped| &scoped.permissions);
+            role.permissions.iter().chain(scoped).for_each(|node| add(node));
+        }
+    }
+
+    let snapshot = snapshot::current();
+    for role in snapshot.roles.values() {
+        role.permissions.iter().for_each(|permission| add(&permission.node));
+    }
+    for player in snapshot.players.values() {
+        player.direct_permissions.iter().for_each(|permission| add(&permission.permission));
+    }
+    nodes
+}
+
+/// Completions for a partly typed node, one segment at a time: known nodes
+/// starting with `input` are cut after the segment being typed, keeping the
+/// `.` when they go on, so `hysterion.pe` suggests `hysterion.perms` and
+/// `hysterion.perms.`. A leading `-` is kept.
+pub fn suggest(input: &str) -> Vec<String> {
+    let (negation, typed) = match input.strip_prefix('-') {
+        Some(typed) => ("-", typed),
+        None => ("", input),
+    };
+
+    let mut suggestions = BTreeSet::new();
+    for node in nodes().iter().filter(|node| node.starts_with(typed)) {
+        let end = node[typed.len()..].find('.').map_or(node.len(), |dot| typed.len() + dot + 1);
+        suggestions.insert(format!("{}{}", negation, &node[..end]));
+    }
+    suggestions.into_iter().collect()
+}
@@ -1,143 +1,356 @@
 // External crate imports
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
+use sqlx::{Row, SqliteConnection};
 use pumpkin::plugin::api::{Context, PermissionChecker};
 use std::sync::Arc;
 use uuid::Uuid;
-use tokio::runtime::Runtime;
 // Internal crate imports
 use crate::db::get_db;
+use audit::{AuditAction, Change};
+use authority::Actor;
+use context::ContextSet;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub mod audit;
+pub mod authority;
+pub mod cache;
+pub mod context;
+pub mod expiry;
+pub mod inheritance;
+pub mod known;
+pub mod node;
+pub mod reconcile;
+pub mod rollback;
+pub mod snapshot;
+pub mod track;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Role {
     pub name: String,
-    pub permissions: Vec<String>,
+    pub permissions: Vec<RolePermission>,
     pub level: i32,
+    pub parents: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlayerPermissions {
     pub uuid: Uuid,
-    pub roles: Vec<String>,
-    pub direct_permissions: Vec<String>,
-}
-
-impl PlayerPermissions {
-    pub async fn has_permission(&self, permission: &str) -> bool {
-        log::info!("[HysterionPerms] Starting permission check for {}: {}", self.uuid, permission);
-        
-        // Check direct permissions first
-        log::info!("[HysterionPerms] Direct permissions: {:?}", self.direct_permissions);
-        for direct_perm in &self.direct_permissions {
-            if check_permission_match(direct_perm, permission) {
-                log::info!(
-                    "[HysterionPerms] Direct permission match: '{}' matches '{}'",
-                    direct_perm,
-                    permission
-                );
-                return true;
-            }
+    pub roles: Vec<RoleMembership>,
+    pub direct_permissions: Vec<DirectPermission>,
+}
+
+/// A node on a role, limited to `contexts` when that is not empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredRolePermission", into = "StoredRolePermission")]
+pub struct RolePermission {
+    pub node: String,
+    pub contexts: ContextSet,
+}
+
+// Serialized shape of a role permission, as stored in audit values and in
+// the former `roles.permissions` JSON column. Unscoped nodes stay plain strings.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StoredRolePermission {
+    Plain(String),
+    Scoped { node: String, context: String },
+}
+
+impl From<StoredRolePermission> for RolePermission {
+    fn from(stored: StoredRolePermission) -> Self {
+        match stored {
+            StoredRolePermission::Plain(node) => RolePermission { node, contexts: ContextSet::new() },
+            StoredRolePermission::Scoped { node, context } => RolePermission {
+                node,
+                contexts: ContextSet::parse(&context).unwrap_or_default(),
+            },
         }
+    }
+}
 
-        // Check role permissions
-        log::info!("[HysterionPerms] Checking roles: {:?}", self.roles);
-        for role_name in &self.roles {
-            match get_role(role_name).await {
-                Ok(role) => {
-                    log::info!("[HysterionPerms] Role '{}' permissions: {:?}", role_name, role.permissions);
-                    for role_perm in &role.permissions {
-                        if check_permission_match(role_perm, permission) {
-                            log::info!(
-                                "[HysterionPerms] Role permission match: '{}' from role '{}' matches '{}'",
-                                role_perm,
-                                role_name,
-                                permission
-                            );
-                            return true;
-                        }
-                    }
-                },
-                Err(e) => log::error!("[HysterionPerms] Failed to get role {}: {}", role_name, e),
+impl From<RolePermission> for StoredRolePermission {
+    fn from(permission: RolePermission) -> Self {
+        if permission.contexts.is_empty() {
+            StoredRolePermission::Plain(permission.node)
+        } else {
+            StoredRolePermission::Scoped {
+                node: permission.node,
+                context: permission.contexts.to_string(),
             }
         }
+    }
+}
+
+/// A player's membership in a role, optionally only until `expires_at`
+/// (unix seconds) and only within `contexts`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoleMembership {
+    pub role: String,
+    pub expires_at: Option<i64>,
+    pub contexts: ContextSet,
+}
 
-        log::info!("[HysterionPerms] No matching permissions found for '{}'", permission);
-        false
+/// A membership taken away by deleting a role. Moved memberships went to the
+/// fallback role; the others were dropped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemovedMembership {
+    pub uuid: String,
+    #[serde(flatten)]
+    pub membership: RoleMembership,
+    pub moved: bool,
+}
+
+/// Everything deleting a role removed, as recorded in the audit log so the
+/// deletion can be undone. Entries recorded before memberships and children
+/// were kept decode with both empty.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeletedRole {
+    #[serde(flatten)]
+    pub role: Role,
+    #[serde(default)]
+    pub fallback: Option<String>,
+    #[serde(default)]
+    pub memberships: Vec<RemovedMembership>,
+    /// Roles that inherited from the deleted one.
+    #[serde(default)]
+    pub children: Vec<String>,
+}
+
+impl RoleMembership {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
     }
 }
 
-// Helper function to check if a permission matches, including wildcard support
-fn check_permission_match(held_permission: &str, required_permission: &str) -> bool {
-    let matches = held_permission == "*" || 
-                 held_permission == required_permission || 
-                 (held_permission.ends_with(".*") && required_permission.starts_with(&held_permission[..held_permission.len()-2]));
-    
-    log::info!(
-        "[HysterionPerms] Permission match check: '{}' against '{}' = {}",
-        held_permission,
-        required_permission,
-        matches
-    );
-    
-    matches
+/// A node granted straight to a player, optionally only until `expires_at`
+/// (unix seconds) and only within `contexts`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirectPermission {
+    pub permission: String,
+    pub expires_at: Option<i64>,
+    pub contexts: ContextSet,
 }
 
+impl DirectPermission {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Creates a role, or sets the level of an existing one.
 #[allow(dead_code)]
-pub async fn init_tables() -> Result<(), sqlx::Error> {
+pub async fn create_role(actor: &Actor, name: &str, level: i32) -> Result<(), sqlx::Error> {
     let db = get_db().await;
+    let previous = get_role(name).await.ok();
+    let mut tx = db.pool.begin().await?;
     
-    // Create roles table
+    // Existing roles keep their id, permissions and parents
     sqlx::query(
-        "CREATE TABLE IF NOT EXISTS roles (
-            id INTEGER PRIMARY KEY,
-            name TEXT NOT NULL UNIQUE,
-            permissions TEXT NOT NULL,
-            level INTEGER NOT NULL
-        )"
+        "INSERT INTO roles (name, level) VALUES ($1, $2)
+         ON CONFLICT(name) DO UPDATE SET level = excluded.level"
     )
-    .execute(&db.pool)
+    .bind(name)
+    .bind(level)
+    .execute(&mut *tx)
     .await?;
 
-    // Create player_roles table (many-to-many relationship)
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS player_roles (
-            id INTEGER PRIMARY KEY,
-            player_uuid TEXT NOT NULL,
-            role_name TEXT NOT NULL,
-            FOREIGN KEY(role_name) REFERENCES roles(name)
-        )"
-    )
-    .execute(&db.pool)
-    .await?;
+    let mut change = Change::new(AuditAction::RoleCreate, name).new_value(level.to_string());
+    if let Some(previous) = previous {
+        change = change.old_value(serde_json::to_string(&previous).unwrap());
+    }
+    audit::record(&mut tx, actor, change).await?;
 
-    // Create player_permissions table (direct permissions)
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS player_permissions (
-            id INTEGER PRIMARY KEY,
-            player_uuid TEXT NOT NULL,
-            permission TEXT NOT NULL
-        )"
-    )
-    .execute(&db.pool)
-    .await?;
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(())
+}
+
+/// Role names are stored as-is and typed in commands, so keep them simple.
+pub fn is_valid_role_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Every role name, sorted.
+pub async fn role_names() -> Result<Vec<String>, sqlx::Error> {
+    let db = get_db().await;
+
+    let names = sqlx::query("SELECT name FROM roles ORDER BY name")
+        .fetch_all(&db.pool)
+        .await?
+        .iter()
+        .map(|row| row.get("name"))
+        .collect();
+    Ok(names)
+}
 
+pub async fn role_exists(name: &str) -> Result<bool, sqlx::Error> {
+    let db = get_db().await;
+
+    let row = sqlx::query("SELECT COUNT(*) AS count FROM roles WHERE name = $1")
+        .bind(name)
+        .fetch_one(&db.pool)
+        .await?;
+    Ok(row.get::<i64, _>("count") > 0)
+}
+
+/// Number of players holding `role_name` directly.
+pub async fn count_role_members(role_name: &str) -> Result<i64, sqlx::Error> {
+    let db = get_db().await;
+
+    let row = sqlx::query("SELECT COUNT(DISTINCT player_uuid) AS count FROM player_roles WHERE role_name = $1")
+        .bind(role_name)
+        .fetch_one(&db.pool)
+        .await?;
+    Ok(row.get("count"))
+}
+
+/// Deletes a role together with its inheritance links. Its memberships move
+/// to `fallback` when given and are dropped otherwise. Returns how many
+/// membership rows were moved or dropped.
+pub async fn delete_role(actor: &Actor, name: &str, fallback: Option<&str>) -> Result<u64, sqlx::Error> {
+    let db = get_db().await;
+    let mut tx = db.pool.begin().await?;
+
+    let Some(deleted) = remove_role(&mut tx, name, fallback).await? else {
+        return Err(sqlx::Error::RowNotFound);
+    };
+    let members = deleted.memberships.len() as u64;
+
+    let mut change = Change::new(AuditAction::RoleDelete, name)
+        .old_value(serde_json::to_string(&deleted).unwrap())
+        .reason(format!("{} membership(s) affected", members));
+    if let Some(fallback) = fallback {
+        change = change.subject(fallback);
+    }
+    audit::record(&mut tx, actor, change).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(members)
+}
+
+/// Deletes a role on `conn`. Members who already hold `fallback` in the same
+/// contexts keep that membership and lose this one; without a fallback every
+/// membership is dropped. Returns what was removed, or `None` when the role
+/// does not exist.
+pub(crate) async fn remove_role(conn: &mut SqliteConnection, name: &str, fallback: Option<&str>) -> Result<Option<DeletedRole>, sqlx::Error> {
+    let Some(role) = read_role(conn, name).await? else {
+        return Ok(None);
+    };
+
+    let rows = sqlx::query("SELECT id, player_uuid, role_name, expires_at, contexts FROM player_roles WHERE role_name = $1 ORDER BY id")
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+    if let Some(fallback) = fallback {
+        sqlx::query("UPDATE OR IGNORE player_roles SET role_name = $1 WHERE role_name = $2")
+            .bind(fallback)
+            .bind(name)
+            .execute(&mut *conn)
+            .await?;
+    }
+    let dropped: Vec<i64> = sqlx::query("DELETE FROM player_roles WHERE role_name = $1 RETURNING id")
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| row.get("id"))
+        .collect();
+    let memberships = rows
+        .iter()
+        .map(|row| RemovedMembership {
+            uuid: row.get("player_uuid"),
+            membership: membership_from_row(row),
+            moved: !dropped.contains(&row.get("id")),
+        })
+        .collect();
+
+    let children: Vec<String> = sqlx::query("SELECT role_name FROM role_parents WHERE parent_name = $1 ORDER BY role_name")
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| row.get("role_name"))
+        .collect();
+    sqlx::query("DELETE FROM role_parents WHERE role_name = $1 OR parent_name = $1")
+        .bind(name)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query("DELETE FROM role_permissions WHERE role_id = (SELECT id FROM roles WHERE name = $1)")
+        .bind(name)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM roles WHERE name = $1")
+        .bind(name)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(Some(DeletedRole { role, fallback: fallback.map(str::to_string), memberships, children }))
+}
+
+/// Renames a role. Memberships and inheritance links follow through their
+/// `ON UPDATE CASCADE` foreign keys.
+pub async fn rename_role(actor: &Actor, old_name: &str, new_name: &str) -> Result<(), sqlx::Error> {
+    let db = get_db().await;
+    let mut tx = db.pool.begin().await?;
+
+    let renamed = sqlx::query("UPDATE roles SET name = $1 WHERE name = $2")
+        .bind(new_name)
+        .bind(old_name)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if renamed == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    audit::record(&mut tx, actor, Change::new(AuditAction::RoleRename, old_name).new_value(new_name)).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
     Ok(())
 }
 
-#[allow(dead_code)]
-pub async fn create_role(name: &str, level: i32) -> Result<(), sqlx::Error> {
+/// Creates `target` with the level, permissions and parents of `source`.
+/// Members are not copied.
+pub async fn clone_role(actor: &Actor, source: &str, target: &str) -> Result<(), sqlx::Error> {
     let db = get_db().await;
-    
-    // Use INSERT OR REPLACE to handle existing roles
+    let mut tx = db.pool.begin().await?;
+
+    let cloned = sqlx::query(
+        "INSERT INTO roles (name, level) SELECT $1, level FROM roles WHERE name = $2"
+    )
+    .bind(target)
+    .bind(source)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if cloned == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+
     sqlx::query(
-        "INSERT OR REPLACE INTO roles (name, permissions, level) VALUES ($1, $2, $3)"
+        "INSERT INTO role_permissions (role_id, permission, contexts)
+         SELECT (SELECT id FROM roles WHERE name = $1), permission, contexts
+         FROM role_permissions WHERE role_id = (SELECT id FROM roles WHERE name = $2)"
     )
-    .bind(name)
-    .bind("[]") // Empty permissions array
-    .bind(level)
-    .execute(&db.pool)
+    .bind(target)
+    .bind(source)
+    .execute(&mut *tx)
     .await?;
 
+    sqlx::query(
+        "INSERT INTO role_parents (role_name, parent_name) SELECT $1, parent_name FROM role_parents WHERE role_name = $2"
+    )
+    .bind(target)
+    .bind(source)
+    .execute(&mut *tx)
+    .await?;
+
+    audit::record(&mut tx, actor, Change::new(AuditAction::RoleClone, target).subject(source)).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
     Ok(())
 }
 
@@ -150,56 +363,175 @@ pub async fn get_role(name: &str) -> Result<Role, sqlx::Error> {
         .fetch_one(&db.pool)
         .await?;
 
-    let permissions: Vec<String> = serde_json::from_str(row.get("permissions"))
-        .unwrap_or_default();
+    let permissions: Vec<RolePermission> = sqlx::query("SELECT permission, contexts FROM role_permissions WHERE role_id = $1 ORDER BY id")
+        .bind(row.get::<i64, _>("id"))
+        .fetch_all(&db.pool)
+        .await?
+        .iter()
+        .map(role_permission_from_row)
+        .collect();
+
+    let parents: Vec<String> = sqlx::query("SELECT parent_name FROM role_parents WHERE role_name = $1")
+        .bind(name)
+        .fetch_all(&db.pool)
+        .await?
+        .into_iter()
+        .map(|row| row.get("parent_name"))
+        .collect();
 
     Ok(Role {
         name: row.get("name"),
         permissions,
         level: i32::from(row.get::<i32, _>("level")),
+        parents,
     })
 }
 
+/// Replaces the parents of a role. Does nothing when they are unchanged.
 #[allow(dead_code)]
-pub async fn add_role_permission(role_name: &str, permission: &str) -> Result<(), sqlx::Error> {
+pub async fn set_role_parents(actor: &Actor, role_name: &str, parents: &[String]) -> Result<(), sqlx::Error> {
     let db = get_db().await;
-    let mut role = get_role(role_name).await?;
-    
-    // Only add permission if it doesn't exist
-    if !role.permissions.contains(&permission.to_string()) {
-        role.permissions.push(permission.to_string());
-        let permissions_json = serde_json::to_string(&role.permissions).unwrap();
-
-        sqlx::query("UPDATE roles SET permissions = $1 WHERE name = $2")
-            .bind(permissions_json)
-            .bind(role_name)
-            .execute(&db.pool)
-            .await?;
+    let mut tx = db.pool.begin().await?;
+
+    let mut previous: Vec<String> = sqlx::query("SELECT parent_name FROM role_parents WHERE role_name = $1")
+        .bind(role_name)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|row| row.get("parent_name"))
+        .collect();
+    let mut requested = parents.to_vec();
+    previous.sort();
+    requested.sort();
+    requested.dedup();
+    if previous == requested {
+        return Ok(());
+    }
+
+    sqlx::query("DELETE FROM role_parents WHERE role_name = $1")
+        .bind(role_name)
+        .execute(&mut *tx)
+        .await?;
+
+    // Unknown parents are skipped rather than failing the foreign key
+    for parent in parents {
+        sqlx::query(
+            "INSERT OR IGNORE INTO role_parents (role_name, parent_name)
+             SELECT $1, $2 WHERE EXISTS (SELECT 1 FROM roles WHERE name = $2)"
+        )
+        .bind(role_name)
+        .bind(parent)
+        .execute(&mut *tx)
+        .await?;
     }
 
+    let change = Change::new(AuditAction::RoleParents, role_name)
+        .old_value(serde_json::to_string(&previous).unwrap())
+        .new_value(serde_json::to_string(&requested).unwrap());
+    audit::record(&mut tx, actor, change).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
     Ok(())
 }
 
+/// Adds `permission` to a role, limited to `contexts` when that is not empty.
+/// Returns `false` when the role already had it in those contexts.
+pub async fn add_role_permission(
+    actor: &Actor,
+    role_name: &str,
+    permission: &str,
+    contexts: &ContextSet,
+) -> Result<bool, sqlx::Error> {
+    let db = get_db().await;
+    let mut tx = db.pool.begin().await?;
+    let role_id = role_id(&mut tx, role_name).await?;
+
+    // The unique constraint makes granting a node twice a no-op
+    let added = sqlx::query("INSERT OR IGNORE INTO role_permissions (role_id, permission, contexts) VALUES ($1, $2, $3)")
+        .bind(role_id)
+        .bind(permission)
+        .bind(contexts.to_string())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if added == 0 {
+        return Ok(false);
+    }
+
+    let change = Change::new(AuditAction::RolePermissionAdd, role_name)
+        .subject(permission)
+        .new_value(contexts.to_string());
+    audit::record(&mut tx, actor, change).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(true)
+}
+
+/// Removes `permission` from a role, only within `contexts` when given and in
+/// every context it was granted in otherwise. Returns whether the role had it.
+pub async fn remove_role_permission(
+    actor: &Actor,
+    role_name: &str,
+    permission: &str,
+    contexts: Option<&ContextSet>,
+) -> Result<bool, sqlx::Error> {
+    let db = get_db().await;
+    let mut tx = db.pool.begin().await?;
+    let role_id = role_id(&mut tx, role_name).await?;
+
+    let removed: Vec<RolePermission> = sqlx::query(
+        "DELETE FROM role_permissions WHERE role_id = $1 AND permission = $2 AND ($3 IS NULL OR contexts = $3)
+         RETURNING permission, contexts"
+    )
+    .bind(role_id)
+    .bind(permission)
+    .bind(contexts.map(ContextSet::to_string))
+    .fetch_all(&mut *tx)
+    .await?
+    .iter()
+    .map(role_permission_from_row)
+    .collect();
+    if removed.is_empty() {
+        return Ok(false);
+    }
+
+    let change = Change::new(AuditAction::RolePermissionRemove, role_name)
+        .subject(permission)
+        .old_value(serde_json::to_string(&removed).unwrap());
+    audit::record(&mut tx, actor, change).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(true)
+}
+
 pub async fn get_player_permissions(uuid: &Uuid) -> Result<PlayerPermissions, sqlx::Error> {
     let db = get_db().await;
     let uuid_str = uuid.to_string();
     
+    // Expired rows may still exist until the cleanup task runs
+    let now = crate::utils::unix_now();
+
     // Get player roles
-    let roles: Vec<String> = sqlx::query("SELECT role_name FROM player_roles WHERE player_uuid = $1")
+    let roles: Vec<RoleMembership> = sqlx::query("SELECT role_name, expires_at, contexts FROM player_roles WHERE player_uuid = $1")
         .bind(&uuid_str)
         .fetch_all(&db.pool)
         .await?
-        .into_iter()
-        .map(|row| row.get("role_name"))
+        .iter()
+        .map(membership_from_row)
+        .filter(|membership| !membership.is_expired(now))
         .collect();
 
     // Get direct permissions
-    let direct_permissions: Vec<String> = sqlx::query("SELECT permission FROM player_permissions WHERE player_uuid = $1")
+    let direct_permissions: Vec<DirectPermission> = sqlx::query("SELECT permission, expires_at, contexts FROM player_permissions WHERE player_uuid = $1")
         .bind(&uuid_str)
         .fetch_all(&db.pool)
         .await?
-        .into_iter()
-        .map(|row| row.get("permission"))
+        .iter()
+        .map(direct_permission_from_row)
+        .filter(|grant| !grant.is_expired(now))
         .collect();
 
     Ok(PlayerPermissions {
@@ -209,66 +541,239 @@ pub async fn get_player_permissions(uuid: &Uuid) -> Result<PlayerPermissions, sq
     })
 }
 
-pub async fn add_player_to_role(uuid: &Uuid, role_name: &str) -> Result<(), sqlx::Error> {
+/// Adds a player to `role_name`, until `expires_at` (unix seconds) when given
+/// and only within `contexts` when that is not empty. Returns `false` when the
+/// player already holds the role in those contexts, leaving it unchanged.
+pub async fn add_player_to_role(
+    actor: &Actor,
+    uuid: &Uuid,
+    role_name: &str,
+    expires_at: Option<i64>,
+    contexts: &ContextSet,
+) -> Result<bool, sqlx::Error> {
     let db = get_db().await;
     let uuid_str = uuid.to_string();
+    let mut tx = db.pool.begin().await?;
     
-    sqlx::query("INSERT INTO player_roles (player_uuid, role_name) VALUES ($1, $2)")
+    let added = sqlx::query("INSERT OR IGNORE INTO player_roles (player_uuid, role_name, expires_at, contexts) VALUES ($1, $2, $3, $4)")
         .bind(&uuid_str)
         .bind(role_name)
-        .execute(&db.pool)
-        .await?;
+        .bind(expires_at)
+        .bind(contexts.to_string())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if added == 0 {
+        return Ok(false);
+    }
 
-    Ok(())
+    let membership = RoleMembership {
+        role: role_name.to_string(),
+        expires_at,
+        contexts: contexts.clone(),
+    };
+    let change = Change::new(AuditAction::PlayerRoleAdd, &uuid_str)
+        .subject(role_name)
+        .new_value(serde_json::to_string(&membership).unwrap());
+    audit::record(&mut tx, actor, change).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(true)
 }
 
-pub async fn add_player_permission(uuid: &Uuid, permission: &str) -> Result<(), sqlx::Error> {
+/// Grants `permission` to a player, until `expires_at` (unix seconds) when given
+/// and only within `contexts` when that is not empty. Returns `false` when the
+/// player already has it in those contexts, leaving it unchanged.
+pub async fn add_player_permission(
+    actor: &Actor,
+    uuid: &Uuid,
+    permission: &str,
+    expires_at: Option<i64>,
+    contexts: &ContextSet,
+) -> Result<bool, sqlx::Error> {
     let db = get_db().await;
     let uuid_str = uuid.to_string();
+    let mut tx = db.pool.begin().await?;
     
-    sqlx::query("INSERT INTO player_permissions (player_uuid, permission) VALUES ($1, $2)")
+    let added = sqlx::query("INSERT OR IGNORE INTO player_permissions (player_uuid, permission, expires_at, contexts) VALUES ($1, $2, $3, $4)")
         .bind(&uuid_str)
         .bind(permission)
-        .execute(&db.pool)
+        .bind(expires_at)
+        .bind(contexts.to_string())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if added == 0 {
+        return Ok(false);
+    }
+
+    let grant = DirectPermission {
+        permission: permission.to_string(),
+        expires_at,
+        contexts: contexts.clone(),
+    };
+    let change = Change::new(AuditAction::PlayerPermissionAdd, &uuid_str)
+        .subject(permission)
+        .new_value(serde_json::to_string(&grant).unwrap());
+    audit::record(&mut tx, actor, change).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(true)
+}
+
+/// Removes every membership of a player in `role_name`, whatever its expiry
+/// or contexts. Returns `false` when the player was not in the role.
+pub async fn remove_player_from_role(actor: &Actor, uuid: &Uuid, role_name: &str) -> Result<bool, sqlx::Error> {
+    let db = get_db().await;
+    let uuid_str = uuid.to_string();
+    let mut tx = db.pool.begin().await?;
+
+    let removed: Vec<RoleMembership> = sqlx::query(
+        "DELETE FROM player_roles WHERE player_uuid = $1 AND role_name = $2
+         RETURNING role_name, expires_at, contexts"
+    )
+    .bind(&uuid_str)
+    .bind(role_name)
+    .fetch_all(&mut *tx)
+    .await?
+    .iter()
+    .map(membership_from_row)
+    .collect();
+
+    if removed.is_empty() {
+        return Ok(false);
+    }
+
+    let change = Change::new(AuditAction::PlayerRoleRemove, &uuid_str)
+        .subject(role_name)
+        .old_value(serde_json::to_string(&removed).unwrap());
+    audit::record(&mut tx, actor, change).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(true)
+}
+
+/// Removes every direct grant of `permission` from a player, whatever its
+/// expiry or contexts. Returns `false` when the player did not have it.
+pub async fn remove_player_permission(actor: &Actor, uuid: &Uuid, permission: &str) -> Result<bool, sqlx::Error> {
+    let db = get_db().await;
+    let uuid_str = uuid.to_string();
+    let mut tx = db.pool.begin().await?;
+
+    let removed: Vec<DirectPermission> = sqlx::query(
+        "DELETE FROM player_permissions WHERE player_uuid = $1 AND permission = $2
+         RETURNING permission, expires_at, contexts"
+    )
+    .bind(&uuid_str)
+    .bind(permission)
+    .fetch_all(&mut *tx)
+    .await?
+    .iter()
+    .map(direct_permission_from_row)
+    .collect();
+
+    if removed.is_empty() {
+        return Ok(false);
+    }
+
+    let change = Change::new(AuditAction::PlayerPermissionRemove, &uuid_str)
+        .subject(permission)
+        .old_value(serde_json::to_string(&removed).unwrap());
+    audit::record(&mut tx, actor, change).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(true)
+}
+
+/// A role as stored, read on `conn` so uncommitted changes are seen. `None`
+/// when the role does not exist.
+pub(crate) async fn read_role(conn: &mut SqliteConnection, name: &str) -> Result<Option<Role>, sqlx::Error> {
+    let Some(row) = sqlx::query("SELECT id, level FROM roles WHERE name = $1")
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let permissions = sqlx::query("SELECT permission, contexts FROM role_permissions WHERE role_id = $1 ORDER BY id")
+        .bind(row.get::<i64, _>("id"))
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(role_permission_from_row)
+        .collect();
+    let parents = sqlx::query("SELECT parent_name FROM role_parents WHERE role_name = $1 ORDER BY parent_name")
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| row.get("parent_name"))
+        .collect();
+
+    Ok(Some(Role { name: name.to_string(), permissions, level: row.get("level"), parents }))
+}
+
+/// Id of the role named `name`, which `role_permissions` rows refer to.
+pub(crate) async fn role_id(conn: &mut SqliteConnection, name: &str) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query("SELECT id FROM roles WHERE name = $1")
+        .bind(name)
+        .fetch_one(&mut *conn)
         .await?;
+    Ok(row.get("id"))
+}
 
-    Ok(())
+pub(crate) fn role_permission_from_row(row: &sqlx::sqlite::SqliteRow) -> RolePermission {
+    RolePermission {
+        node: row.get("permission"),
+        contexts: ContextSet::parse(row.get("contexts")).unwrap_or_default(),
+    }
 }
 
-pub struct HysterionPermissionChecker {
-    runtime: &'static Runtime,
+pub(crate) fn membership_from_row(row: &sqlx::sqlite::SqliteRow) -> RoleMembership {
+    RoleMembership {
+        role: row.get("role_name"),
+        expires_at: row.get("expires_at"),
+        contexts: ContextSet::parse(row.get("contexts")).unwrap_or_default(),
+    }
+}
+
+pub(crate) fn direct_permission_from_row(row: &sqlx::sqlite::SqliteRow) -> DirectPermission {
+    DirectPermission {
+        permission: row.get("permission"),
+        expires_at: row.get("expires_at"),
+        contexts: ContextSet::parse(row.get("contexts")).unwrap_or_default(),
+    }
 }
 
+/// Answers checks from the cached effective permissions built from the
+/// in-memory snapshot, so it never waits on SQLite or a runtime.
+pub struct HysterionPermissionChecker;
+
 impl HysterionPermissionChecker {
     pub fn new() -> Self {
-        Self {
-            runtime: crate::get_runtime(),
-        }
+        Self
     }
 }
 
 impl PermissionChecker for HysterionPermissionChecker {
     fn check_permission(&self, uuid: &Uuid, permission: &str) -> bool {
-        self.runtime.block_on(async {
-            log::info!("[HysterionPerms] Checking permission '{}' for player {}", permission, uuid);
-            
-            match get_player_permissions(uuid).await {
-                Ok(player_perms) => {
-                    log::info!("[HysterionPerms] Found permissions for player: {:?}", player_perms);
-                    player_perms.has_permission(permission).await
-                },
-                Err(e) => {
-                    log::error!("[HysterionPerms] Failed to check permissions for {}: {}", uuid, e);
-                    false
-                }
-            }
-        })
+        log::debug!("[HysterionPerms] Checking permission '{}' for player {}", permission, uuid);
+        known::record_checked(permission);
+        cache::resolve(uuid).has_permission(permission, &context::current_contexts(uuid))
     }
 }
 
 pub async fn init_permission_system(server: &Context) {
     log::info!("[HysterionPerms] Initializing permission system");
+    if let Err(e) = snapshot::reload().await {
+        log::error!("[HysterionPerms] Failed to load permission snapshot: {}", e);
+    }
     let checker = Arc::new(HysterionPermissionChecker::new());
     server.register_permission_checker(checker).await;
     log::info!("[HysterionPerms] Permission system initialized and checker registered");
-} 
\ No newline at end of file
+}
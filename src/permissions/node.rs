@@ -0,0 +1,486 @@
+// Permission node parsing and precedence resolution.
+//
+// A stored node is either a grant (`hysterion.mod.*`) or a denial prefixed
+// with `-` (`-hysterion.mod.ban`). When several stored nodes match the
+// permission being checked, the winner is picked with these rules, in order:
+//
+// 1. More specific beats less specific (more literal segments wins).
+// 2. Player-direct nodes beat role nodes, and a role's own nodes beat the
+//    ones it inherits (nearer parents before further ones).
+// 3. Deny beats allow.
+//
+// Patterns are compared segment by segment (segments are separated by `.`):
+//
+// | Pattern            | Matches                      | Does not match                 |
+// |--------------------|------------------------------|--------------------------------|
+// | `*` or `**`        | every node                   |                                |
+// | `a.b`              | `a.b`                        | `a`, `a.b.c`, `a.bc`           |
+// | `a.*`              | `a.b`, `a.c`                 | `a`, `a.b.c`, `ab.c`           |
+// | `a.**`             | `a.b`, `a.b.c`               | `a`, `ab.c`                    |
+// | `a.*.c`            | `a.b.c`, `a.x.c`             | `a.c`, `a.b.d`, `a.b.c.d`      |
+// | `a.{b,c}`          | `a.b`, `a.c`                 | `a.d`, `a.b.c`                 |
+// | `a.b*`             | `a.b`, `a.bc`                | `a.c`, `a.b.c`                 |
+//
+// - `*` inside a segment matches any run of characters within that segment
+//   only; it never crosses a `.`.
+// - `**` matches one or more trailing segments and is only special as the
+//   last segment. A pattern is never satisfied by its own parent, so neither
+//   `a.*` nor `a.**` grants `a`.
+// - `{x,y}` lists alternatives for part of a single segment and may be
+//   combined with `*` (`a.{kick,ban*}`).
+//
+// Specificity scores each pattern segment: literal 3, alternatives 2, partial
+// glob 1, bare `*` or trailing `**` 0.
+use std::cmp::Reverse;
+
+/// Where a stored node came from, used to break specificity ties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrantSource {
+    /// A role node; `depth` is 0 for roles the player holds and grows by one
+    /// per inheritance step.
+    Role { depth: usize },
+    Direct,
+}
+
+impl GrantSource {
+    fn priority(&self) -> (bool, Reverse<usize>) {
+        match self {
+            GrantSource::Direct => (true, Reverse(0)),
+            GrantSource::Role { depth } => (false, Reverse(*depth)),
+        }
+    }
+}
+
+/// A stored permission node split into its pattern and polarity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermissionNode<'a> {
+    pub pattern: &'a str,
+    pub negated: bool,
+}
+
+impl<'a> PermissionNode<'a> {
+    pub fn parse(raw: &'a str) -> Self {
+        match raw.strip_prefix('-') {
+            Some(pattern) => Self { pattern, negated: true },
+            None => Self { pattern: raw, negated: false },
+        }
+    }
+
+    /// Whether the pattern can match more than one node.
+    pub fn is_wildcard(&self) -> bool {
+        self.pattern.contains(['*', '{'])
+    }
+
+    /// The first segment, e.g. `worldedit` for `-worldedit.wand`.
+    pub fn namespace(&self) -> &'a str {
+        self.pattern.split('.').next().unwrap_or(self.pattern)
+    }
+
+    pub fn matches(&self, required_permission: &str) -> bool {
+        check_permission_match(self.pattern, required_permission)
+    }
+
+    /// Sum of per-segment scores, so `a.b.*` outranks `a.**` and `*`.
+    pub fn specificity(&self) -> usize {
+        self.pattern
+            .split('.')
+            .map(|segment| match segment {
+                "*" | "**" => 0,
+                _ if segment.contains('*') => 1,
+                _ if segment.contains('{') => 2,
+                _ => 3,
+            })
+            .sum()
+    }
+}
+
+// Helper function to check if a permission matches, comparing dot-separated segments
+pub fn check_permission_match(held_permission: &str, required_permission: &str) -> bool {
+    let matches = pattern_matches(held_permission, required_permission);
+
+    log::trace!(
+        "[HysterionPerms] Permission match check: '{}' against '{}' = {}",
+        held_permission,
+        required_permission,
+        matches
+    );
+
+    matches
+}
+
+/// The matching behind [`check_permission_match`], without its logging.
+pub fn pattern_matches(held_permission: &str, required_permission: &str) -> bool {
+    let pattern: Vec<&str> = held_permission.split('.').collect();
+    let segments: Vec<&str> = required_permission.split('.').collect();
+
+    match pattern.split_last() {
+        _ if held_permission == "*" => true,
+        Some((&"**", prefix)) => {
+            segments.len() > prefix.len() &&
+                prefix.iter().zip(&segments).all(|(p, s)| segment_matches(p, s))
+        },
+        _ => {
+            pattern.len() == segments.len() &&
+                pattern.iter().zip(&segments).all(|(p, s)| segment_matches(p, s))
+        },
+    }
+}
+
+/// Whether `held` matches every node `wanted` can match, so that holding
+/// `held` is enough to grant `wanted`. Unlike [`pattern_matches`], which reads
+/// `wanted` as a literal node, this keeps `a.*` from covering `a.**`: a
+/// trailing `**` is only covered by a `**` at the same or a shorter prefix,
+/// and every `{x,y}` alternative and glob in `wanted` must be covered.
+pub fn pattern_covers(held: &str, wanted: &str) -> bool {
+    if held == "*" || held == "**" {
+        return true;
+    }
+    if wanted == "*" {
+        return false;
+    }
+
+    let held: Vec<&str> = held.split('.').collect();
+    let wanted: Vec<&str> = wanted.split('.').collect();
+    let covers_all = |held: &[&str], wanted: &[&str]| held.iter().zip(wanted).all(|(h, w)| segment_covers(h, w));
+    match (held.split_last(), wanted.split_last()) {
+        (Some((&"**", held_prefix)), Some((&"**", wanted_prefix))) => {
+            held_prefix.len() <= wanted_prefix.len() && covers_all(held_prefix, wanted_prefix)
+        },
+        (_, Some((&"**", _))) => false,
+        (Some((&"**", held_prefix)), _) => held_prefix.len() < wanted.len() && covers_all(held_prefix, &wanted),
+        _ => held.len() == wanted.len() && covers_all(&held, &wanted),
+    }
+}
+
+// Every spelling of the wanted segment must be matched by the held one
+fn segment_covers(held: &str, wanted: &str) -> bool {
+    expand_braces(wanted).iter().all(|wanted| {
+        if !wanted.contains('*') {
+            return segment_matches(held, wanted);
+        }
+        expand_braces(held).iter().any(|held| glob_covers(held, wanted))
+    })
+}
+
+// Glob containment, kept conservative: a held glob covers a wanted one when
+// it is `*`, the same glob, or a `prefix*` the wanted glob starts with
+fn glob_covers(held: &str, wanted: &str) -> bool {
+    held == "*"
+        || held == wanted
+        || held.strip_suffix('*').is_some_and(|prefix| !prefix.contains('*') && wanted.starts_with(prefix))
+}
+
+/// Whether some node is matched by both `a` and `b`. Segments are compared
+/// pairwise, and a trailing `**` overlaps any remaining tail of at least one
+/// segment.
+pub fn patterns_overlap(a: &str, b: &str) -> bool {
+    if a == "*" || b == "*" {
+        return true;
+    }
+    let a: Vec<&str> = a.split('.').collect();
+    let b: Vec<&str> = b.split('.').collect();
+    tails_overlap(&a, &b)
+}
+
+fn tails_overlap(a: &[&str], b: &[&str]) -> bool {
+    match (a, b) {
+        ([], []) => true,
+        (["**"], rest) | (rest, ["**"]) => !rest.is_empty(),
+        ([], _) | (_, []) => false,
+        ([a, a_rest @ ..], [b, b_rest @ ..]) => segments_overlap(a, b) && tails_overlap(a_rest, b_rest),
+    }
+}
+
+// Some spelling of each segment matches a common string
+fn segments_overlap(a: &str, b: &str) -> bool {
+    let (a, b) = (expand_braces(a), expand_braces(b));
+    a.iter().any(|a| b.iter().any(|b| globs_overlap(a.as_bytes(), b.as_bytes())))
+}
+
+// Two globs overlap when, walking both, each `*` can either end or absorb the
+// next symbol of the other glob until both are used up
+fn globs_overlap(a: &[u8], b: &[u8]) -> bool {
+    // reachable[i][j]: `a[i..]` and `b[j..]` can still match a common suffix
+    let mut reachable = vec![vec![false; b.len() + 1]; a.len() + 1];
+    reachable[a.len()][b.len()] = true;
+    for i in (0..=a.len()).rev() {
+        for j in (0..=b.len()).rev() {
+            if i == a.len() && j == b.len() {
+                continue;
+            }
+            let a_star = i < a.len() && a[i] == b'*';
+            let b_star = j < b.len() && b[j] == b'*';
+            reachable[i][j] = (a_star && (reachable[i + 1][j] || (j < b.len() && reachable[i][j + 1])))
+                || (b_star && (reachable[i][j + 1] || (i < a.len() && reachable[i + 1][j])))
+                || (i < a.len() && j < b.len() && !a_star && !b_star && a[i] == b[j] && reachable[i + 1][j + 1]);
+        }
+    }
+    reachable[0][0]
+}
+
+/// Checks that a stored node is well formed, returning the first problem:
+/// empty segments, characters outside `a-z A-Z 0-9 _ - * { } ,`, a `**`
+/// that is not the whole last segment, or misplaced braces.
+pub fn check_syntax(raw: &str) -> Result<(), String> {
+    let pattern = raw.strip_prefix('-').unwrap_or(raw);
+    if pattern.is_empty() {
+        return Err("node is empty".to_string());
+    }
+    if pattern.starts_with('-') {
+        return Err("node is negated more than once".to_string());
+    }
+
+    let segments: Vec<&str> = pattern.split('.').collect();
+    for (index, segment) in segments.iter().enumerate() {
+        if segment.is_empty() {
+            return Err("node has an empty segment".to_string());
+        }
+        if let Some(c) = segment.chars().find(|c| !c.is_ascii_alphanumeric() && !"_-*{},".contains(*c)) {
+            return Err(format!("'{}' is not allowed in a node", c));
+        }
+        if segment.contains("**") && (*segment != "**" || index + 1 != segments.len()) {
+            return Err("'**' is only allowed as the whole last segment".to_string());
+        }
+
+        let mut in_braces = false;
+        let mut alternative_len = 0;
+        for c in segment.chars() {
+            match c {
+                '{' if in_braces => return Err("braces cannot be nested".to_string()),
+                '{' => {
+                    in_braces = true;
+                    alternative_len = 0;
+                },
+                '}' | ',' if !in_braces => return Err(format!("'{}' outside braces", c)),
+                '}' | ',' if alternative_len == 0 => return Err("empty alternative in braces".to_string()),
+                '}' => in_braces = false,
+                ',' => alternative_len = 0,
+                _ => alternative_len += 1,
+            }
+        }
+        if in_braces {
+            return Err("'{' is never closed".to_string());
+        }
+    }
+    Ok(())
+}
+
+fn segment_matches(pattern: &str, segment: &str) -> bool {
+    if !pattern.contains(['*', '{']) {
+        return pattern == segment;
+    }
+    expand_braces(pattern).iter().any(|alternative| glob_match(alternative, segment))
+}
+
+/// Expands `{x,y}` groups into every alternative spelling of the segment.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_owned()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|i| open + i) else {
+        return vec![pattern.to_owned()];
+    };
+
+    let (head, tail) = (&pattern[..open], &pattern[close + 1..]);
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|alternative| expand_braces(&format!("{}{}{}", head, alternative, tail)))
+        .collect()
+}
+
+/// Matches a single segment where `*` stands for any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text) = (pattern.as_bytes(), text.as_bytes());
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, consumed)) = backtrack {
+            p = star + 1;
+            t = consumed + 1;
+            backtrack = Some((star, consumed + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|b| *b == b'*')
+}
+
+/// Picks the deciding node for `required_permission` out of `grants`.
+///
+/// Returns `None` when no node matches, otherwise whether the winning node
+/// allows the permission.
+pub fn resolve<'a, I>(grants: I, required_permission: &str) -> Option<bool>
+where
+    I: IntoIterator<Item = (&'a str, GrantSource)>,
+{
+    grants
+        .into_iter()
+        .map(|(raw, source)| (PermissionNode::parse(raw), source, raw))
+        .filter(|(node, _, _)| node.matches(required_permission))
+        .max_by_key(|(node, source, _)| (node.specificity(), source.priority(), node.negated))
+        .map(|(node, source, raw)| {
+            log::debug!(
+                "[HysterionPerms] '{}' ({:?}) decides '{}': {}",
+                raw,
+                source,
+                required_permission,
+                if node.negated { "deny" } else { "allow" }
+            );
+            !node.negated
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stored nodes, the permission checked, and the expected decision
+    type ResolveCase<'a> = (&'a [(&'a str, GrantSource)], &'a str, Option<bool>);
+
+    #[test]
+    fn pattern_matches_follows_the_table() {
+        let cases = [
+            ("*", "a", true),
+            ("*", "a.b.c", true),
+            ("**", "a.b", true),
+            ("a.b", "a.b", true),
+            ("a.b", "a", false),
+            ("a.b", "a.b.c", false),
+            ("a.b", "a.bc", false),
+            ("a.*", "a.b", true),
+            ("a.*", "a", false),
+            ("a.*", "a.b.c", false),
+            ("a.*", "ab.c", false),
+            ("a.**", "a.b", true),
+            ("a.**", "a.b.c", true),
+            ("a.**", "a", false),
+            ("a.**", "ab.c", false),
+            ("a.*.c", "a.b.c", true),
+            ("a.*.c", "a.c", false),
+            ("a.*.c", "a.b.d", false),
+            ("a.*.c", "a.b.c.d", false),
+            ("a.{b,c}", "a.b", true),
+            ("a.{b,c}", "a.c", true),
+            ("a.{b,c}", "a.d", false),
+            ("a.{b,c}", "a.b.c", false),
+            ("a.b*", "a.b", true),
+            ("a.b*", "a.bc", true),
+            ("a.b*", "a.c", false),
+            ("a.b*", "a.b.c", false),
+            ("a.{kick,ban*}", "a.banip", true),
+            ("a.{kick,ban*}", "a.mute", false),
+        ];
+        for (pattern, node, expected) in cases {
+            assert_eq!(pattern_matches(pattern, node), expected, "'{}' against '{}'", pattern, node);
+        }
+    }
+
+    #[test]
+    fn pattern_covers_only_what_it_contains() {
+        let cases = [
+            ("*", "a.**", true),
+            ("a.**", "a.**", true),
+            ("a.**", "a.b.**", true),
+            ("a.**", "a.*", true),
+            ("a.*", "a.**", false),
+            ("a.b.**", "a.**", false),
+            ("a.*", "a.b", true),
+            ("a.*", "a.{b,c}", true),
+            ("a.{b,c}", "a.{b,c}", true),
+            ("a.{b,c}", "a.{b,d}", false),
+            ("a.b*", "a.bc*", true),
+            ("a.bc*", "a.b*", false),
+            ("a.b", "a.*", false),
+            ("a.b", "*", false),
+        ];
+        for (held, wanted, expected) in cases {
+            assert_eq!(pattern_covers(held, wanted), expected, "'{}' covering '{}'", held, wanted);
+        }
+    }
+
+    #[test]
+    fn patterns_overlap_when_some_node_matches_both() {
+        let cases = [
+            ("a.b.*", "a.*.c", true),
+            ("a.b.*", "a.*.*", true),
+            ("a.**", "a.b.c.d", true),
+            ("a.**", "a", false),
+            ("a.b.**", "a.*.c", true),
+            ("a.b.**", "a.c.**", false),
+            ("a.*", "a.b.c", false),
+            ("a.{b,c}", "a.c*", true),
+            ("a.{b,c}", "a.d*", false),
+            ("a.b*", "a.*c", true),
+            ("a.b*c", "a.bx*", true),
+            ("a.b*c", "a.*d", false),
+            ("*", "a.b.c", true),
+            ("a.b", "a.b", true),
+            ("a.b", "a.c", false),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(patterns_overlap(a, b), expected, "'{}' and '{}'", a, b);
+            assert_eq!(patterns_overlap(b, a), expected, "'{}' and '{}'", b, a);
+        }
+    }
+
+    #[test]
+    fn check_syntax_reports_malformed_nodes() {
+        let cases = [
+            ("a.b", true),
+            ("-a.b", true),
+            ("a.*", true),
+            ("a.**", true),
+            ("*", true),
+            ("a.{b,c*}.d", true),
+            ("my_plugin.use-it", true),
+            ("", false),
+            ("-", false),
+            ("--a", false),
+            ("a..b", false),
+            ("a.", false),
+            ("a b", false),
+            ("a.**.b", false),
+            ("a.b**", false),
+            ("a.{b,{c}}", false),
+            ("a.{b,}", false),
+            ("a.{}", false),
+            ("a.b}", false),
+            ("a.b,c", false),
+            ("a.{b", false),
+        ];
+        for (raw, valid) in cases {
+            assert_eq!(check_syntax(raw).is_ok(), valid, "'{}': {:?}", raw, check_syntax(raw));
+        }
+    }
+
+    #[test]
+    fn resolve_applies_precedence_rules() {
+        let role = GrantSource::Role { depth: 0 };
+        let inherited = GrantSource::Role { depth: 1 };
+        let cases: [ResolveCase; 9] = [
+            (&[], "a.b", None),
+            (&[("a.c", role)], "a.b", None),
+            (&[("a.*", role)], "a.b", Some(true)),
+            // More specific wins, whatever the source or polarity
+            (&[("-a.*", GrantSource::Direct), ("a.b", inherited)], "a.b", Some(true)),
+            (&[("a.**", role), ("-a.b.*", inherited)], "a.b.c", Some(false)),
+            (&[("a.{b,c}", role), ("-a.*", role)], "a.b", Some(true)),
+            // Direct beats role, and nearer roles beat inherited ones
+            (&[("-a.b", role), ("a.b", GrantSource::Direct)], "a.b", Some(true)),
+            (&[("-a.b", inherited), ("a.b", role)], "a.b", Some(true)),
+            // Deny wins a full tie
+            (&[("a.b", role), ("-a.b", role)], "a.b", Some(false)),
+        ];
+        for (grants, permission, expected) in cases {
+            assert_eq!(resolve(grants.iter().copied(), permission), expected, "{:?} deciding '{}'", grants, permission);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// Bringing the stored roles in line with config.toml at startup and on reload,
+// according to the configured `reconcile` mode. Changes go through the normal
+// mutators as the system actor, so only real differences reach the audit log.
+use std::collections::HashSet;
+
+use super::authority::Actor;
+use super::inheritance;
+use super::context::ContextSet;
+use super::{
+    add_role_permission, create_role, delete_role, get_role, remove_role_permission, role_names, set_role_parents,
+    RolePermission,
+};
+use crate::config::{ConfigValue, ReconcileMode, RoleConfig};
+
+/// What reconciling one role changed, for the startup log.
+#[derive(Debug, Default)]
+struct RoleDiff {
+    created: bool,
+    level: Option<(i32, i32)>,
+    added: Vec<String>,
+    removed: Vec<String>,
+    kept: usize,
+}
+
+impl RoleDiff {
+    fn log(&self, name: &str) {
+        let mut parts = Vec::new();
+        if self.created {
+            parts.push("created".to_string());
+        }
+        if let Some((old, new)) = self.level {
+            parts.push(format!("level {} -> {}", old, new));
+        }
+        if !self.added.is_empty() {
+            parts.push(format!("added {}", self.added.join(", ")));
+        }
+        if !self.removed.is_empty() {
+            parts.push(format!("removed {}", self.removed.join(", ")));
+        }
+        parts.push(format!("kept {} node(s)", self.kept));
+        log::info!("[HysterionPerms] Role {}: {}", name, parts.join("; "));
+    }
+}
+
+// Nodes in the log, e.g. `hysterion.mod.kick [world=creative]`
+fn describe(permission: &RolePermission) -> String {
+    if permission.contexts.is_empty() {
+        permission.node.clone()
+    } else {
+        format!("{} [{}]", permission.node, permission.contexts)
+    }
+}
+
+/// Every node the config gives a role, scoped ones included.
+fn configured_permissions(role: &RoleConfig) -> Vec<RolePermission> {
+    let unscoped = role.permissions.iter().map(|node| RolePermission {
+        node: node.clone(),
+        contexts: ContextSet::new(),
+    });
+    let scoped = role.scoped.iter().flat_map(|scoped| {
+        scoped.permissions.iter().map(|node| RolePermission {
+            node: node.clone(),
+            contexts: scoped.context.clone(),
+        })
+    });
+    unscoped.chain(scoped).collect()
+}
+
+/// Reconciles the roles of a validated config. A config with an inheritance
+/// cycle is still refused here, before anything is written.
+pub async fn apply(config: &ConfigValue) -> Result<(), String> {
+    let graph = config.roles.iter()
+        .map(|(name, role)| (name.as_str(), role.inherits.iter().map(String::as_str).collect()))
+        .collect();
+    if let Some(cycle) = inheritance::find_cycle(&graph) {
+        return Err(format!("Role inheritance cycle: {}", cycle.join(" -> ")));
+    }
+
+    reconcile(config).await.map_err(|e| format!("Failed to reconcile roles with config: {}", e))
+}
+
+/// Applies the configured roles to the database and logs what changed.
+/// Failures are logged per role so one bad entry does not stop the rest.
+pub async fn reconcile(config: &ConfigValue) -> Result<(), sqlx::Error> {
+    let mode = config.reconcile;
+    let existing: HashSet<String> = role_names().await?.into_iter().collect();
+    log::info!(
+        "[HysterionPerms] Reconciling {} configured role(s) with {} stored role(s) (mode: {})",
+        config.roles.len(), existing.len(), mode.as_str()
+    );
+
+    let mut names: Vec<&String> = config.roles.keys().collect();
+    names.sort();
+
+    for name in &names {
+        let stored = existing.contains(name.as_str());
+        match reconcile_role(mode, name, &config.roles[name.as_str()], stored).await {
+            Ok(diff) => diff.log(name),
+            Err(e) => log::error!("Failed to reconcile role {}: {}", name, e),
+        }
+    }
+
+    // Parents are linked once every configured role exists
+    let known: HashSet<String> = role_names().await?.into_iter().collect();
+    for name in &names {
+        let stored = existing.contains(name.as_str());
+        if let Err(e) = reconcile_parents(mode, name, &config.roles[name.as_str()], stored, &known).await {
+            log::warn!("Failed to set parents of role {}: {}", name, e);
+        }
+    }
+
+    if mode == ReconcileMode::Config {
+        let mut removed: Vec<&String> = existing.iter().filter(|name| !config.roles.contains_key(*name)).collect();
+        removed.sort();
+        for name in removed {
+            match delete_role(&Actor::System, name, None).await {
+                Ok(members) => log::info!(
+                    "[HysterionPerms] Role {}: deleted, not in config ({} membership(s) dropped)",
+                    name, members
+                ),
+                Err(e) => log::error!("Failed to delete role {}: {}", name, e),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn reconcile_role(mode: ReconcileMode, name: &str, role_config: &RoleConfig, stored: bool) -> Result<RoleDiff, sqlx::Error> {
+    let actor = Actor::System;
+    let mut diff = RoleDiff::default();
+    let configured = configured_permissions(role_config);
+
+    if !stored {
+        create_role(&actor, name, role_config.level).await?;
+        diff.created = true;
+    } else if mode == ReconcileMode::Database {
+        diff.kept = get_role(name).await?.permissions.len();
+        return Ok(diff);
+    }
+
+    let role = get_role(name).await?;
+    if role.level != role_config.level {
+        create_role(&actor, name, role_config.level).await?;
+        diff.level = Some((role.level, role_config.level));
+    }
+
+    for permission in &configured {
+        if role.permissions.contains(permission) {
+            continue;
+        }
+        add_role_permission(&actor, name, &permission.node, &permission.contexts).await?;
+        diff.added.push(describe(permission));
+    }
+
+    for permission in &role.permissions {
+        if mode == ReconcileMode::Config && !configured.contains(permission) {
+            remove_role_permission(&actor, name, &permission.node, Some(&permission.contexts)).await?;
+            diff.removed.push(describe(permission));
+        } else {
+            diff.kept += 1;
+        }
+    }
+
+    Ok(diff)
+}
+
+// Unknown parents are left out; they are reported when the config is loaded
+async fn reconcile_parents(
+    mode: ReconcileMode,
+    name: &str,
+    role_config: &RoleConfig,
+    stored: bool,
+    known: &HashSet<String>,
+) -> Result<(), sqlx::Error> {
+    let current = get_role(name).await?.parents;
+    let configured = role_config.inherits.iter().filter(|parent| known.contains(*parent)).cloned();
+    let parents: Vec<String> = match mode {
+        ReconcileMode::Database if stored => return Ok(()),
+        ReconcileMode::Merge => current.iter().cloned().chain(configured).collect(),
+        _ => configured.collect(),
+    };
+
+    let mut before = current.clone();
+    let mut after = parents.clone();
+    before.sort();
+    after.sort();
+    after.dedup();
+    if before != after {
+        log::info!(
+            "[HysterionPerms] Role {}: parents [{}] -> [{}]",
+            name, before.join(", "), after.join(", ")
+        );
+        set_role_parents(&Actor::System, name, &parents).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,578 @@
+// Reverting recorded changes by applying their inverse.
+use std::fmt;
+use sqlx::{Row, SqliteConnection};
+use uuid::Uuid;
+
+use super::audit::{self, AuditAction, AuditEntry, Change};
+use super::authority::{Actor, Denied};
+use super::context::ContextSet;
+use super::{
+    read_role, remove_role, role_id, snapshot, track, DeletedRole, DirectPermission, Role, RoleMembership,
+    RolePermission,
+};
+use crate::db::get_db;
+
+#[derive(Debug)]
+pub enum RollbackError {
+    /// Entry `id` cannot be reverted; nothing was applied.
+    Irreversible { id: i64, reason: String },
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for RollbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RollbackError::Irreversible { id, reason } => write!(f, "Entry #{} cannot be undone: {}", id, reason),
+            RollbackError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for RollbackError {
+    fn from(e: sqlx::Error) -> Self {
+        RollbackError::Database(e)
+    }
+}
+
+/// The operation that reverts one audit entry.
+#[derive(Debug, Clone)]
+pub enum Inverse {
+    /// Recreates a deleted role with its memberships and child links.
+    RestoreRole(DeletedRole),
+    /// Deletes a created role. A role brought back by an undo is deleted as
+    /// it was before, its members moving to the same fallback again; any
+    /// other role must have no members.
+    DropRole { role: String, restored: Option<DeletedRole> },
+    /// Puts back the level a create of an existing role replaced.
+    SetLevel { role: String, level: i32 },
+    RenameRole { from: String, to: String },
+    SetParents { role: String, parents: Vec<String> },
+    RemoveRolePermission { role: String, permission: RolePermission },
+    AddRolePermissions { role: String, permissions: Vec<RolePermission> },
+    RemoveMembership { uuid: String, membership: RoleMembership },
+    AddMemberships { uuid: String, memberships: Vec<RoleMembership> },
+    /// Moves a player from `current` back to `previous`, or out of
+    /// `current` when they were not on the track before.
+    MoveMembership { uuid: String, current: String, previous: Option<String> },
+    RemoveDirect { uuid: String, grant: DirectPermission },
+    AddDirect { uuid: String, grants: Vec<DirectPermission> },
+}
+
+/// One entry to revert together with how.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub entry: AuditEntry,
+    pub inverse: Inverse,
+}
+
+/// Works out how to revert each entry, in the order given. Fails without
+/// reverting anything if any entry cannot be reverted.
+pub fn plan(entries: Vec<AuditEntry>) -> Result<Vec<Step>, RollbackError> {
+    entries
+        .into_iter()
+        .map(|entry| Ok(Step { inverse: inverse_of(&entry)?, entry }))
+        .collect()
+}
+
+fn inverse_of(entry: &AuditEntry) -> Result<Inverse, RollbackError> {
+    let irreversible = |reason: &str| RollbackError::Irreversible { id: entry.id, reason: reason.to_string() };
+    let target = entry.target.clone();
+
+    let Some(action) = AuditAction::parse(&entry.action) else {
+        return Err(irreversible("unknown action"));
+    };
+
+    let inverse = match action {
+        AuditAction::RoleCreate if entry.old_value.is_some() => Inverse::SetLevel {
+            role: target,
+            level: decode::<Role>(entry, &entry.old_value)?.level,
+        },
+        // A create recorded by undoing a delete keeps what it restored
+        AuditAction::RoleCreate => Inverse::DropRole {
+            role: target,
+            restored: entry.new_value.as_deref().and_then(|value| serde_json::from_str(value).ok()),
+        },
+        AuditAction::RoleClone => Inverse::DropRole { role: target, restored: None },
+        AuditAction::RoleDelete => Inverse::RestoreRole(decode(entry, &entry.old_value)?),
+        AuditAction::RoleRename => Inverse::RenameRole {
+            from: entry.new_value.clone().ok_or_else(|| irreversible("the new name was not recorded"))?,
+            to: target,
+        },
+        AuditAction::RoleParents => Inverse::SetParents { role: target, parents: decode(entry, &entry.old_value)? },
+        AuditAction::RolePermissionAdd => Inverse::RemoveRolePermission {
+            role: target,
+            permission: RolePermission {
+                node: entry.subject.clone().ok_or_else(|| irreversible("the node was not recorded"))?,
+                contexts: ContextSet::parse(entry.new_value.as_deref().unwrap_or_default())
+                    .map_err(|_| irreversible("the recorded contexts are malformed"))?,
+            },
+        },
+        AuditAction::RolePermissionRemove => Inverse::AddRolePermissions { role: target, permissions: decode(entry, &entry.old_value)? },
+        AuditAction::PlayerRoleAdd => Inverse::RemoveMembership { uuid: target, membership: decode(entry, &entry.new_value)? },
+        AuditAction::PlayerRoleRemove => Inverse::AddMemberships { uuid: target, memberships: decode(entry, &entry.old_value)? },
+        AuditAction::PlayerRoleMove => Inverse::MoveMembership {
+            uuid: target,
+            current: entry.new_value.clone().ok_or_else(|| irreversible("the new role was not recorded"))?,
+            previous: entry.old_value.clone(),
+        },
+        AuditAction::PlayerPermissionAdd => Inverse::RemoveDirect { uuid: target, grant: decode(entry, &entry.new_value)? },
+        AuditAction::PlayerPermissionRemove => Inverse::AddDirect { uuid: target, grants: decode(entry, &entry.old_value)? },
+        AuditAction::PlayerRoleExpire | AuditAction::PlayerPermissionExpire => {
+            return Err(irreversible("expired grants are not restored"));
+        },
+        AuditAction::Denied => return Err(irreversible("the change was refused, so nothing was applied")),
+    };
+    Ok(inverse)
+}
+
+fn decode<T: serde::de::DeserializeOwned>(entry: &AuditEntry, value: &Option<String>) -> Result<T, RollbackError> {
+    value
+        .as_deref()
+        .and_then(|value| serde_json::from_str(value).ok())
+        .ok_or_else(|| RollbackError::Irreversible { id: entry.id, reason: "the recorded value is missing or malformed".to_string() })
+}
+
+impl fmt::Display for Inverse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inverse::RestoreRole(deleted) => write!(
+                f,
+                "restore role {} (level {}) with {} membership(s)",
+                deleted.role.name, deleted.role.level, deleted.memberships.len()
+            ),
+            Inverse::DropRole { role, restored: Some(DeletedRole { fallback: Some(fallback), .. }) } => {
+                write!(f, "delete role {} and move its members back to {}", role, fallback)
+            },
+            Inverse::DropRole { role, .. } => write!(f, "delete role {}", role),
+            Inverse::SetLevel { role, level } => write!(f, "set the level of {} back to {}", role, level),
+            Inverse::RenameRole { from, to } => write!(f, "rename role {} back to {}", from, to),
+            Inverse::SetParents { role, parents } => write!(f, "set parents of {} to [{}]", role, parents.join(", ")),
+            Inverse::RemoveRolePermission { role, permission } => write!(f, "remove {} from role {}", permission.node, role),
+            Inverse::AddRolePermissions { role, permissions } => {
+                let nodes: Vec<&str> = permissions.iter().map(|permission| permission.node.as_str()).collect();
+                write!(f, "add {} back to role {}", nodes.join(", "), role)
+            },
+            Inverse::RemoveMembership { uuid, membership } => write!(f, "remove role {} from {}", membership.role, uuid),
+            Inverse::AddMemberships { uuid, memberships } => {
+                let roles: Vec<&str> = memberships.iter().map(|membership| membership.role.as_str()).collect();
+                write!(f, "give role {} back to {}", roles.join(", "), uuid)
+            },
+            Inverse::MoveMembership { uuid, current, previous: Some(previous) } => {
+                write!(f, "move {} from {} back to {}", uuid, current, previous)
+            },
+            Inverse::MoveMembership { uuid, current, previous: None } => write!(f, "remove role {} from {}", current, uuid),
+            Inverse::RemoveDirect { uuid, grant } => write!(f, "remove {} from {}", grant.permission, uuid),
+            Inverse::AddDirect { uuid, grants } => {
+                let nodes: Vec<&str> = grants.iter().map(|grant| grant.permission.as_str()).collect();
+                write!(f, "give {} back to {}", nodes.join(", "), uuid)
+            },
+        }
+    }
+}
+
+impl Inverse {
+    /// Roles whose config.toml entry this changes, for write-through.
+    pub fn roles(&self) -> Vec<&str> {
+        match self {
+            Inverse::RestoreRole(deleted) => {
+                let mut roles = vec![deleted.role.name.as_str()];
+                roles.extend(deleted.children.iter().map(String::as_str));
+                roles
+            },
+            Inverse::RenameRole { from, to } => vec![from, to],
+            Inverse::DropRole { role, .. }
+            | Inverse::SetLevel { role, .. }
+            | Inverse::SetParents { role, .. }
+            | Inverse::RemoveRolePermission { role, .. }
+            | Inverse::AddRolePermissions { role, .. } => vec![role],
+            Inverse::RemoveMembership { .. }
+            | Inverse::AddMemberships { .. }
+            | Inverse::MoveMembership { .. }
+            | Inverse::RemoveDirect { .. }
+            | Inverse::AddDirect { .. } => Vec::new(),
+        }
+    }
+
+    /// Applies the same delegation rules as the command that made the change.
+    pub fn authorize(&self, actor: &Actor) -> Result<(), Denied> {
+        let check_player = |uuid: &str| match Uuid::parse_str(uuid) {
+            Ok(parsed) => actor.check_player(&parsed, uuid),
+            Err(_) => Ok(()),
+        };
+        match self {
+            Inverse::RestoreRole(deleted) => {
+                actor.check_level(deleted.role.level)?;
+                deleted.fallback.as_deref().map_or(Ok(()), |fallback| actor.check_role(fallback))
+            },
+            Inverse::DropRole { role, restored } => {
+                actor.check_role(role)?;
+                match restored.as_ref().and_then(|restored| restored.fallback.as_deref()) {
+                    Some(fallback) => actor.check_role(fallback),
+                    None => Ok(()),
+                }
+            },
+            Inverse::SetLevel { role, level } => {
+                actor.check_role(role)?;
+                actor.check_level(*level)
+            },
+            Inverse::SetParents { role, .. } | Inverse::RemoveRolePermission { role, .. } => actor.check_role(role),
+            Inverse::RenameRole { from, .. } => actor.check_role(from),
+            Inverse::AddRolePermissions { role, permissions } => {
+                actor.check_role(role)?;
+                permissions.iter().try_for_each(|permission| actor.check_node(&permission.node, &permission.contexts))
+            },
+            Inverse::RemoveMembership { uuid, membership } => {
+                check_player(uuid)?;
+                actor.check_role(&membership.role)
+            },
+            Inverse::AddMemberships { uuid, memberships } => {
+                check_player(uuid)?;
+                memberships.iter().try_for_each(|membership| actor.check_role(&membership.role))
+            },
+            Inverse::MoveMembership { uuid, current, previous } => {
+                check_player(uuid)?;
+                actor.check_role(current)?;
+                previous.as_deref().map_or(Ok(()), |previous| actor.check_role(previous))
+            },
+            Inverse::RemoveDirect { uuid, .. } => check_player(uuid),
+            Inverse::AddDirect { uuid, grants } => {
+                check_player(uuid)?;
+                grants.iter().try_for_each(|grant| actor.check_node(&grant.permission, &grant.contexts))
+            },
+        }
+    }
+
+    /// Applies the inverse on `conn` and returns the changes to record for
+    /// it, one for every row it restored, so the undo can be undone in turn.
+    async fn apply(&self, conn: &mut SqliteConnection, id: i64) -> Result<Vec<Change>, RollbackError> {
+        let irreversible = |reason: String| RollbackError::Irreversible { id, reason };
+
+        let change = match self {
+            Inverse::RestoreRole(deleted) => {
+                let role = &deleted.role;
+                if read_role(conn, &role.name).await?.is_some() {
+                    return Err(irreversible(format!("a role named {} exists again", role.name)));
+                }
+                sqlx::query("INSERT INTO roles (name, level) VALUES ($1, $2)")
+                    .bind(&role.name)
+                    .bind(role.level)
+                    .execute(&mut *conn)
+                    .await?;
+                let role_id = role_id(conn, &role.name).await?;
+                insert_role_permissions(conn, role_id, &role.permissions).await?;
+                write_parents(conn, &role.name, &role.parents).await?;
+                for child in &deleted.children {
+                    sqlx::query(
+                        "INSERT OR IGNORE INTO role_parents (role_name, parent_name)
+                         SELECT $1, $2 WHERE EXISTS (SELECT 1 FROM roles WHERE name = $1)"
+                    )
+                    .bind(child)
+                    .bind(&role.name)
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                for removed in &deleted.memberships {
+                    let membership = &removed.membership;
+                    if let (true, Some(fallback)) = (removed.moved, &deleted.fallback) {
+                        sqlx::query(
+                            "DELETE FROM player_roles WHERE id = (
+                                SELECT id FROM player_roles
+                                WHERE player_uuid = $1 AND role_name = $2 AND contexts = $3 AND expires_at IS $4
+                                LIMIT 1
+                            )"
+                        )
+                        .bind(&removed.uuid)
+                        .bind(fallback)
+                        .bind(membership.contexts.to_string())
+                        .bind(membership.expires_at)
+                        .execute(&mut *conn)
+                        .await?;
+                    }
+                    sqlx::query("INSERT OR IGNORE INTO player_roles (player_uuid, role_name, expires_at, contexts) VALUES ($1, $2, $3, $4)")
+                        .bind(&removed.uuid)
+                        .bind(&role.name)
+                        .bind(membership.expires_at)
+                        .bind(membership.contexts.to_string())
+                        .execute(&mut *conn)
+                        .await?;
+                }
+                Change::new(AuditAction::RoleCreate, &role.name).new_value(serde_json::to_string(deleted).unwrap())
+            },
+            Inverse::DropRole { role, restored } => {
+                if restored.is_none() {
+                    let members: i64 = sqlx::query("SELECT COUNT(*) AS count FROM player_roles WHERE role_name = $1")
+                        .bind(role)
+                        .fetch_one(&mut *conn)
+                        .await?
+                        .get("count");
+                    if members > 0 {
+                        return Err(irreversible(format!("role {} still has {} membership(s)", role, members)));
+                    }
+                }
+                let fallback = restored.as_ref().and_then(|restored| restored.fallback.as_deref());
+                let Some(deleted) = remove_role(conn, role, fallback).await? else {
+                    return Err(irreversible(format!("role {} no longer exists", role)));
+                };
+                let mut change = Change::new(AuditAction::RoleDelete, role).old_value(serde_json::to_string(&deleted).unwrap());
+                if let Some(fallback) = fallback {
+                    change = change.subject(fallback);
+                }
+                change
+            },
+            Inverse::SetLevel { role, level } => {
+                let Some(current) = read_role(conn, role).await? else {
+                    return Err(irreversible(format!("role {} no longer exists", role)));
+                };
+                sqlx::query("UPDATE roles SET level = $1 WHERE name = $2")
+                    .bind(level)
+                    .bind(role)
+                    .execute(&mut *conn)
+                    .await?;
+                // Recorded like a create of an existing role, so it undoes the same way
+                Change::new(AuditAction::RoleCreate, role)
+                    .old_value(serde_json::to_string(&current).unwrap())
+                    .new_value(level.to_string())
+            },
+            Inverse::RenameRole { from, to } => {
+                let taken: i64 = sqlx::query("SELECT COUNT(*) AS count FROM roles WHERE name = $1")
+                    .bind(to)
+                    .fetch_one(&mut *conn)
+                    .await?
+                    .get("count");
+                if taken > 0 {
+                    return Err(irreversible(format!("a role named {} exists again", to)));
+                }
+                let renamed = sqlx::query("UPDATE roles SET name = $1 WHERE name = $2")
+                    .bind(to)
+                    .bind(from)
+                    .execute(&mut *conn)
+                    .await?
+                    .rows_affected();
+                if renamed == 0 {
+                    return Err(irreversible(format!("role {} no longer exists", from)));
+                }
+                Change::new(AuditAction::RoleRename, from).new_value(to)
+            },
+            Inverse::SetParents { role, parents } => {
+                write_parents(conn, role, parents).await?;
+                Change::new(AuditAction::RoleParents, role).new_value(serde_json::to_string(parents).unwrap())
+            },
+            Inverse::RemoveRolePermission { role, permission } => {
+                let role_id = match role_id(conn, role).await {
+                    Ok(role_id) => role_id,
+                    Err(sqlx::Error::RowNotFound) => return Err(irreversible(format!("role {} no longer exists", role))),
+                    Err(e) => return Err(e.into()),
+                };
+                let removed = sqlx::query("DELETE FROM role_permissions WHERE role_id = $1 AND permission = $2 AND contexts = $3")
+                    .bind(role_id)
+                    .bind(&permission.node)
+                    .bind(permission.contexts.to_string())
+                    .execute(&mut *conn)
+                    .await?
+                    .rows_affected();
+                if removed == 0 {
+                    return Ok(Vec::new());
+                }
+                Change::new(AuditAction::RolePermissionRemove, role)
+                    .subject(&permission.node)
+                    .old_value(serde_json::to_string(&vec![permission]).unwrap())
+            },
+            Inverse::AddRolePermissions { role, permissions } => {
+                let role_id = match role_id(conn, role).await {
+                    Ok(role_id) => role_id,
+                    Err(sqlx::Error::RowNotFound) => return Err(irreversible(format!("role {} no longer exists", role))),
+                    Err(e) => return Err(e.into()),
+                };
+                return Ok(insert_role_permissions(conn, role_id, permissions)
+                    .await?
+                    .into_iter()
+                    .map(|permission| {
+                        Change::new(AuditAction::RolePermissionAdd, role)
+                            .subject(&permission.node)
+                            .new_value(permission.contexts.to_string())
+                    })
+                    .collect());
+            },
+            Inverse::RemoveMembership { uuid, membership } => {
+                sqlx::query(
+                    "DELETE FROM player_roles WHERE id = (
+                        SELECT id FROM player_roles
+                        WHERE player_uuid = $1 AND role_name = $2 AND contexts = $3 AND expires_at IS $4
+                        LIMIT 1
+                    )"
+                )
+                .bind(uuid)
+                .bind(&membership.role)
+                .bind(membership.contexts.to_string())
+                .bind(membership.expires_at)
+                .execute(&mut *conn)
+                .await?;
+                Change::new(AuditAction::PlayerRoleRemove, uuid)
+                    .subject(&membership.role)
+                    .old_value(serde_json::to_string(&vec![membership]).unwrap())
+            },
+            Inverse::AddMemberships { uuid, memberships } => {
+                let mut changes = Vec::new();
+                for membership in memberships {
+                    let exists: i64 = sqlx::query("SELECT COUNT(*) AS count FROM roles WHERE name = $1")
+                        .bind(&membership.role)
+                        .fetch_one(&mut *conn)
+                        .await?
+                        .get("count");
+                    if exists == 0 {
+                        return Err(irreversible(format!("role {} no longer exists", membership.role)));
+                    }
+                    let inserted = sqlx::query("INSERT OR IGNORE INTO player_roles (player_uuid, role_name, expires_at, contexts) VALUES ($1, $2, $3, $4)")
+                        .bind(uuid)
+                        .bind(&membership.role)
+                        .bind(membership.expires_at)
+                        .bind(membership.contexts.to_string())
+                        .execute(&mut *conn)
+                        .await?
+                        .rows_affected();
+                    if inserted > 0 {
+                        changes.push(
+                            Change::new(AuditAction::PlayerRoleAdd, uuid)
+                                .subject(&membership.role)
+                                .new_value(serde_json::to_string(membership).unwrap()),
+                        );
+                    }
+                }
+                return Ok(changes);
+            },
+            Inverse::MoveMembership { uuid, current, previous } => {
+                match previous {
+                    Some(previous) => {
+                        if track::move_conflicts(conn, uuid, current, previous).await? {
+                            return Err(irreversible(format!("{} already has role {} again", uuid, previous)));
+                        }
+                        sqlx::query("UPDATE player_roles SET role_name = $1 WHERE player_uuid = $2 AND role_name = $3")
+                            .bind(previous)
+                            .bind(uuid)
+                            .bind(current)
+                            .execute(&mut *conn)
+                            .await?;
+                    },
+                    None => {
+                        sqlx::query(
+                            "DELETE FROM player_roles WHERE id = (
+                                SELECT id FROM player_roles WHERE player_uuid = $1 AND role_name = $2 LIMIT 1
+                            )"
+                        )
+                        .bind(uuid)
+                        .bind(current)
+                        .execute(&mut *conn)
+                        .await?;
+                    },
+                }
+                let mut change = Change::new(AuditAction::PlayerRoleMove, uuid).old_value(current);
+                if let Some(previous) = previous {
+                    change = change.subject(previous).new_value(previous);
+                }
+                change
+            },
+            Inverse::RemoveDirect { uuid, grant } => {
+                sqlx::query(
+                    "DELETE FROM player_permissions WHERE id = (
+                        SELECT id FROM player_permissions
+                        WHERE player_uuid = $1 AND permission = $2 AND contexts = $3 AND expires_at IS $4
+                        LIMIT 1
+                    )"
+                )
+                .bind(uuid)
+                .bind(&grant.permission)
+                .bind(grant.contexts.to_string())
+                .bind(grant.expires_at)
+                .execute(&mut *conn)
+                .await?;
+                Change::new(AuditAction::PlayerPermissionRemove, uuid)
+                    .subject(&grant.permission)
+                    .old_value(serde_json::to_string(&vec![grant]).unwrap())
+            },
+            Inverse::AddDirect { uuid, grants } => {
+                let mut changes = Vec::new();
+                for grant in grants {
+                    let inserted = sqlx::query("INSERT OR IGNORE INTO player_permissions (player_uuid, permission, expires_at, contexts) VALUES ($1, $2, $3, $4)")
+                        .bind(uuid)
+                        .bind(&grant.permission)
+                        .bind(grant.expires_at)
+                        .bind(grant.contexts.to_string())
+                        .execute(&mut *conn)
+                        .await?
+                        .rows_affected();
+                    if inserted > 0 {
+                        changes.push(
+                            Change::new(AuditAction::PlayerPermissionAdd, uuid)
+                                .subject(&grant.permission)
+                                .new_value(serde_json::to_string(grant).unwrap()),
+                        );
+                    }
+                }
+                return Ok(changes);
+            },
+        };
+        Ok(vec![change])
+    }
+}
+
+// Returns the permissions the role did not already have
+async fn insert_role_permissions<'a>(
+    conn: &mut SqliteConnection,
+    role_id: i64,
+    permissions: &'a [RolePermission],
+) -> Result<Vec<&'a RolePermission>, sqlx::Error> {
+    let mut inserted = Vec::new();
+    for permission in permissions {
+        let added = sqlx::query("INSERT OR IGNORE INTO role_permissions (role_id, permission, contexts) VALUES ($1, $2, $3)")
+            .bind(role_id)
+            .bind(&permission.node)
+            .bind(permission.contexts.to_string())
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        if added > 0 {
+            inserted.push(permission);
+        }
+    }
+    Ok(inserted)
+}
+
+// Parents that no longer exist are skipped
+async fn write_parents(conn: &mut SqliteConnection, role: &str, parents: &[String]) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM role_parents WHERE role_name = $1")
+        .bind(role)
+        .execute(&mut *conn)
+        .await?;
+    for parent in parents {
+        sqlx::query(
+            "INSERT OR IGNORE INTO role_parents (role_name, parent_name)
+             SELECT $1, $2 WHERE EXISTS (SELECT 1 FROM roles WHERE name = $2)"
+        )
+        .bind(role)
+        .bind(parent)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Applies every step in order inside one transaction. Each revert is
+/// recorded as its own entry with the reason `undo #<id>`, which also keeps
+/// it from being reverted twice.
+pub async fn apply(actor: &Actor, steps: &[Step]) -> Result<usize, RollbackError> {
+    let db = get_db().await;
+    let mut tx = db.pool.begin().await?;
+
+    for step in steps {
+        if audit::is_undone(&mut tx, step.entry.id).await? {
+            return Err(RollbackError::Irreversible { id: step.entry.id, reason: "it was already undone".to_string() });
+        }
+        for change in step.inverse.apply(&mut tx, step.entry.id).await? {
+            audit::record(&mut tx, actor, change.reason(format!("undo #{}", step.entry.id))).await?;
+        }
+    }
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(steps.len())
+}
@@ -0,0 +1,214 @@
+// In-memory copy of every role and player grant, used to answer permission
+// checks without touching SQLite.
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, RwLock};
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::cache::{self, EffectivePermissions, Grant};
+use super::context::ContextSet;
+use super::node::GrantSource;
+use super::{inheritance, role_permission_from_row, DirectPermission, PlayerPermissions, Role, RoleMembership};
+use crate::db::get_db;
+
+#[derive(Debug, Default)]
+pub struct PermissionSnapshot {
+    /// Increases with every load; an older load never replaces a newer one.
+    pub version: u64,
+    pub roles: HashMap<String, Role>,
+    pub players: HashMap<Uuid, PlayerPermissions>,
+}
+
+static SNAPSHOT: LazyLock<RwLock<Arc<PermissionSnapshot>>> = LazyLock::new(Default::default);
+static NEXT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+impl PermissionSnapshot {
+    async fn load() -> Result<Self, sqlx::Error> {
+        let version = NEXT_VERSION.fetch_add(1, Ordering::AcqRel);
+        let db = get_db().await;
+
+        let mut roles: HashMap<String, Role> = sqlx::query("SELECT name, level FROM roles")
+            .fetch_all(&db.pool)
+            .await?
+            .into_iter()
+            .map(|row| {
+                let name: String = row.get("name");
+                let role = Role {
+                    name: name.clone(),
+                    permissions: Vec::new(),
+                    level: row.get("level"),
+                    parents: Vec::new(),
+                };
+                (name, role)
+            })
+            .collect();
+
+        let role_permissions = sqlx::query(
+            "SELECT roles.name AS role_name, permission, contexts FROM role_permissions
+             JOIN roles ON roles.id = role_permissions.role_id ORDER BY role_permissions.id"
+        )
+        .fetch_all(&db.pool)
+        .await?;
+        for row in role_permissions {
+            if let Some(role) = roles.get_mut(row.get::<&str, _>("role_name")) {
+                role.permissions.push(role_permission_from_row(&row));
+            }
+        }
+
+        for row in sqlx::query("SELECT role_name, parent_name FROM role_parents")
+            .fetch_all(&db.pool)
+            .await?
+        {
+            if let Some(role) = roles.get_mut(row.get::<&str, _>("role_name")) {
+                role.parents.push(row.get("parent_name"));
+            }
+        }
+
+        let mut players: HashMap<Uuid, PlayerPermissions> = HashMap::new();
+
+        for row in sqlx::query("SELECT player_uuid, role_name, expires_at, contexts FROM player_roles")
+            .fetch_all(&db.pool)
+            .await?
+        {
+            let membership = RoleMembership {
+                role: row.get("role_name"),
+                expires_at: row.get("expires_at"),
+                contexts: ContextSet::parse(row.get("contexts")).unwrap_or_default(),
+            };
+            match player_entry(&mut players, row.get("player_uuid")) {
+                Some(perms) => perms.roles.push(membership),
+                None => log::warn!("[HysterionPerms] Skipping role row with invalid uuid"),
+            }
+        }
+
+        for row in sqlx::query("SELECT player_uuid, permission, expires_at, contexts FROM player_permissions")
+            .fetch_all(&db.pool)
+            .await?
+        {
+            let grant = DirectPermission {
+                permission: row.get("permission"),
+                expires_at: row.get("expires_at"),
+                contexts: ContextSet::parse(row.get("contexts")).unwrap_or_default(),
+            };
+            match player_entry(&mut players, row.get("player_uuid")) {
+                Some(perms) => perms.direct_permissions.push(grant),
+                None => log::warn!("[HysterionPerms] Skipping permission row with invalid uuid"),
+            }
+        }
+
+        Ok(PermissionSnapshot { version, roles, players })
+    }
+
+    /// Flattens a player's direct nodes and every held or inherited role node
+    /// into one list tagged with where each node came from.
+    pub fn effective_permissions(&self, player: &PlayerPermissions) -> EffectivePermissions {
+        let mut grants: Vec<Grant> = player.direct_permissions.iter().map(|grant| Grant {
+            node: grant.permission.clone(),
+            source: GrantSource::Direct,
+            expires_at: grant.expires_at,
+            contexts: grant.contexts.clone(),
+        }).collect();
+
+        // Roles that are missing still count, so creating them later invalidates us
+        let mut depends_on: HashSet<String> = HashSet::new();
+
+        // Each membership is walked on its own so everything it brings in
+        // shares its expiry and contexts
+        for membership in &player.roles {
+            depends_on.insert(membership.role.clone());
+
+            let roles = inheritance::resolve_roles(std::slice::from_ref(&membership.role), |name| self.roles.get(name));
+            for (role, depth) in roles {
+                depends_on.insert(role.name.clone());
+                depends_on.extend(role.parents.iter().cloned());
+                // Nodes scoped to contexts that contradict the membership can never apply
+                grants.extend(role.permissions.iter().filter_map(|perm| {
+                    Some(Grant {
+                        node: perm.node.clone(),
+                        source: GrantSource::Role { depth },
+                        expires_at: membership.expires_at,
+                        contexts: membership.contexts.merged(&perm.contexts)?,
+                    })
+                }));
+            }
+        }
+
+        EffectivePermissions { grants, depends_on }
+    }
+
+    pub fn effective_permissions_of(&self, uuid: &Uuid) -> EffectivePermissions {
+        match self.players.get(uuid) {
+            Some(player) => self.effective_permissions(player),
+            None => EffectivePermissions::default(),
+        }
+    }
+}
+
+fn player_entry<'a>(
+    players: &'a mut HashMap<Uuid, PlayerPermissions>,
+    uuid_str: &str,
+) -> Option<&'a mut PlayerPermissions> {
+    let uuid = Uuid::parse_str(uuid_str).ok()?;
+    Some(players.entry(uuid).or_insert_with(|| PlayerPermissions {
+        uuid,
+        roles: Vec::new(),
+        direct_permissions: Vec::new(),
+    }))
+}
+
+pub fn current() -> Arc<PermissionSnapshot> {
+    SNAPSHOT.read().unwrap().clone()
+}
+
+/// Reads every role and grant from the database and swaps the result in.
+///
+/// Cache entries are dropped for exactly the players and roles that differ
+/// between the old and the new snapshot.
+pub async fn reload() -> Result<(), sqlx::Error> {
+    let snapshot = Arc::new(PermissionSnapshot::load().await?);
+
+    let previous = {
+        let mut current = SNAPSHOT.write().unwrap();
+        if current.version > snapshot.version {
+            return Ok(());
+        }
+        std::mem::replace(&mut *current, snapshot.clone())
+    };
+
+    for role_name in changed_keys(&previous.roles, &snapshot.roles) {
+        cache::invalidate_role(role_name);
+    }
+    for uuid in changed_keys(&previous.players, &snapshot.players) {
+        cache::invalidate_player(uuid);
+    }
+
+    log::info!(
+        "[HysterionPerms] Loaded permission snapshot v{} ({} roles, {} players)",
+        snapshot.version,
+        snapshot.roles.len(),
+        snapshot.players.len()
+    );
+    Ok(())
+}
+
+/// Refreshes the snapshot on the plugin runtime without waiting for it.
+pub fn schedule_refresh() {
+    crate::get_runtime().spawn(async {
+        if let Err(e) = reload().await {
+            log::error!("[HysterionPerms] Failed to refresh permission snapshot: {}", e);
+        }
+    });
+}
+
+fn changed_keys<'a, K, V>(old: &'a HashMap<K, V>, new: &'a HashMap<K, V>) -> Vec<&'a K>
+where
+    K: std::hash::Hash + Eq,
+    V: PartialEq,
+{
+    old.iter()
+        .filter(|(key, value)| new.get(*key) != Some(*value))
+        .map(|(key, _)| key)
+        .chain(new.keys().filter(|key| !old.contains_key(*key)))
+        .collect()
+}
@@ -0,0 +1,121 @@
+// Promotion tracks: ordered role lists a player moves along one step at a time.
+use sqlx::SqliteConnection;
+use uuid::Uuid;
+
+use super::audit::{self, AuditAction, Change};
+use super::authority::Actor;
+use super::{snapshot, PlayerPermissions};
+use crate::db::get_db;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackDirection {
+    Promote,
+    Demote,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackMove {
+    /// The player's role is swapped; `from` is `None` when promoting a player
+    /// who holds no role on the track onto its first role.
+    Moved { from: Option<String>, to: String },
+    /// Already on the highest role of the track.
+    AtTop(String),
+    /// Already on the lowest role of the track.
+    AtBottom(String),
+    /// Demoting a player who holds no role on the track.
+    NotOnTrack,
+}
+
+/// Works out where one step along `track` takes a player, starting from the
+/// highest track role they hold.
+pub fn plan_move(held: &PlayerPermissions, track: &[String], direction: TrackDirection) -> TrackMove {
+    let position = track
+        .iter()
+        .rposition(|role| held.roles.iter().any(|membership| &membership.role == role));
+
+    match (direction, position) {
+        (TrackDirection::Promote, None) => match track.first() {
+            Some(first) => TrackMove::Moved { from: None, to: first.clone() },
+            None => TrackMove::NotOnTrack,
+        },
+        (TrackDirection::Promote, Some(index)) => match track.get(index + 1) {
+            Some(next) => TrackMove::Moved { from: Some(track[index].clone()), to: next.clone() },
+            None => TrackMove::AtTop(track[index].clone()),
+        },
+        (TrackDirection::Demote, None) => TrackMove::NotOnTrack,
+        (TrackDirection::Demote, Some(0)) => TrackMove::AtBottom(track[0].clone()),
+        (TrackDirection::Demote, Some(index)) => TrackMove::Moved {
+            from: Some(track[index].clone()),
+            to: track[index - 1].clone(),
+        },
+    }
+}
+
+/// Applies a [`TrackMove::Moved`]; other moves change nothing.
+///
+/// The old membership is replaced in place, so its expiry and contexts carry
+/// over to the new role. Returns `false`, changing nothing, when the player
+/// already holds the new role in the contexts the move would carry over.
+pub async fn apply_move(actor: &Actor, uuid: &Uuid, track_name: &str, step: &TrackMove) -> Result<bool, sqlx::Error> {
+    let TrackMove::Moved { from, to } = step else {
+        return Ok(true);
+    };
+
+    let db = get_db().await;
+    let uuid_str = uuid.to_string();
+    let mut tx = db.pool.begin().await?;
+    match from {
+        Some(from) => {
+            if move_conflicts(&mut tx, &uuid_str, from, to).await? {
+                return Ok(false);
+            }
+            sqlx::query("UPDATE player_roles SET role_name = $1 WHERE player_uuid = $2 AND role_name = $3")
+                .bind(to)
+                .bind(&uuid_str)
+                .bind(from)
+                .execute(&mut *tx)
+                .await?;
+        },
+        None => {
+            let added = sqlx::query("INSERT OR IGNORE INTO player_roles (player_uuid, role_name, contexts) VALUES ($1, $2, '')")
+                .bind(&uuid_str)
+                .bind(to)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            if added == 0 {
+                return Ok(false);
+            }
+        }
+    }
+
+    let mut change = Change::new(AuditAction::PlayerRoleMove, &uuid_str)
+        .subject(to)
+        .new_value(to)
+        .reason(format!("track {}", track_name));
+    if let Some(from) = from {
+        change = change.old_value(from);
+    }
+    audit::record(&mut tx, actor, change).await?;
+
+    tx.commit().await?;
+    snapshot::schedule_refresh();
+    Ok(true)
+}
+
+/// Whether moving a player's `from` memberships to `to` would collide with a
+/// `to` membership they already hold in the same contexts.
+pub(crate) async fn move_conflicts(conn: &mut SqliteConnection, uuid: &str, from: &str, to: &str) -> Result<bool, sqlx::Error> {
+    let conflict = sqlx::query(
+        "SELECT 1 FROM player_roles moved
+         JOIN player_roles held ON held.player_uuid = moved.player_uuid AND held.contexts = moved.contexts
+         WHERE moved.player_uuid = $1 AND moved.role_name = $2 AND held.role_name = $3
+         LIMIT 1"
+    )
+    .bind(uuid)
+    .bind(from)
+    .bind(to)
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(conflict.is_some())
+}
@@ -13,4 +13,100 @@ pub fn error_colour() -> RGBColor {
 #[allow(dead_code)]
 pub fn neutral_colour() -> RGBColor {
     RGBColor::new(105, 200, 255)
-} 
\ No newline at end of file
+} 
+/// Current time as a unix timestamp in seconds.
+pub fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Longest duration accepted, about 100 years, so adding one to the current
+/// time can never overflow.
+pub const MAX_DURATION: i64 = 100 * 365 * 24 * 60 * 60;
+
+/// Parses durations such as `30m`, `7d` or `1d12h` into seconds.
+///
+/// Supported units are `s`, `m`, `h`, `d` and `w`; anything longer than
+/// [`MAX_DURATION`] is rejected.
+pub fn parse_duration(input: &str) -> Option<i64> {
+    let mut total: i64 = 0;
+    let mut digits = String::new();
+
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        let amount: i64 = digits.parse().ok()?;
+        total = total.checked_add(amount.checked_mul(unit)?)?;
+        digits.clear();
+    }
+
+    if !digits.is_empty() || total == 0 || total > MAX_DURATION {
+        return None;
+    }
+    Some(total)
+}
+
+/// Formats a number of seconds as e.g. `6d 23h`, keeping the two largest units.
+pub fn format_duration(seconds: i64) -> String {
+    const UNITS: [(i64, &str); 4] = [(24 * 60 * 60, "d"), (60 * 60, "h"), (60, "m"), (1, "s")];
+
+    let mut remaining = seconds.max(0);
+    let parts: Vec<String> = UNITS
+        .iter()
+        .filter_map(|(size, suffix)| {
+            let amount = remaining / size;
+            remaining %= size;
+            (amount > 0).then(|| format!("{}{}", amount, suffix))
+        })
+        .take(2)
+        .collect();
+
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Number of single-character edits needed to turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Candidates close enough to `input` to be a likely typo, closest first.
+pub fn closest_matches<'a>(input: &str, candidates: &'a [String], limit: usize) -> Vec<&'a str> {
+    let input = input.to_lowercase();
+    let max_distance = (input.chars().count() / 3).max(2);
+
+    let mut matches: Vec<(usize, &str)> = candidates
+        .iter()
+        .map(|candidate| (edit_distance(&input, &candidate.to_lowercase()), candidate.as_str()))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort();
+    matches.into_iter().take(limit).map(|(_, candidate)| candidate).collect()
+}